@@ -12,18 +12,149 @@ import {
     worker: PublicKey
     totalQuantity: Number
     actualStep: Number
+    notifyProgram: PublicKey | null
+    status: Number
+    rounding: RoundingPolicy
+    frozen: Boolean
+    payoutDelay: Number
+    pendingAmount: Number
+    pendingSince: Number
+    memos: String[]
+    authorizedReleasers: PublicKey[]
+    lastActivityTs: Number
+    stepAmounts: Number[]
+    closeRequestedAt: Number
+    requireProof: Boolean
+    proofs: Uint8Array[]
+    totalDisbursed: Number
+    bump: Number
+    approvers: PublicKey[]
+    approvalThreshold: Number
+    stepApprovals: PublicKey[]
+    autoClose: Boolean
+    refundAllowlist: PublicKey[]
+    evidenceUris: String[]
+    notificationTag: String
+    maxSinglePayout: Number
+    rentTreasury: PublicKey | null
+    delegatedIncrement: { delegate: PublicKey, expiry: Number } | null
+    dualIncrement: Boolean
+    pendingSignerChange: {
+      authorizedReleasers: PublicKey[],
+      approvers: PublicKey[],
+      timelockExpiry: Number,
+    } | null
+    breachCompensationBps: Number
+    stepRecipients: PublicKey[]
+    registryProgram: PublicKey | null
+    stepBps: Number[]
+    confirmationsRequired: Number
+    stepConfirmers: Number
+    allowSkip: Boolean
+    paidSteps: Number
+    pendingStep: Number
+    category: ContractCategory
+    paidOrder: Number[]
   }
 
+  export enum RoundingPolicy {
+    FavorWorker = 0,
+    FavorOwner = 1,
+    Banker = 2,
+  }
+
+  export enum ContractCategory {
+    Milestone = 0,
+    Bounty = 1,
+    Retainer = 2,
+  }
+
+  const rentRecipientSchema = { enum: [
+    { struct: { owner: { struct: {} } } },
+    { struct: { treasury: { array: { type: 'u8', len: 32 } } } },
+  ]};
+
   const contractDataSchema = { struct: {
     contract_id: 'string',
     owner: { array: { type: 'u8', len: 32 } },
     worker: { array: { type: 'u8', len: 32 } },
     total_quantity: 'u64',
     actual_step: 'u64',
+    notify_program: { option: { array: { type: 'u8', len: 32 } } },
+    status: 'u8',
+    rounding: 'u8',
+    frozen: 'bool',
+    payout_delay: 'i64',
+    pending_amount: 'u64',
+    pending_since: 'i64',
+    memos: { array: { type: 'string' } },
+    authorized_releasers: { array: { type: { array: { type: 'u8', len: 32 } } } },
+    last_activity_ts: 'i64',
+    step_amounts: { array: { type: 'u64' } },
+    close_requested_at: 'i64',
+    require_proof: 'bool',
+    proofs: { array: { type: { array: { type: 'u8', len: 32 } } } },
+    total_disbursed: 'u64',
+    bump: 'u8',
+    approvers: { array: { type: { array: { type: 'u8', len: 32 } } } },
+    approval_threshold: 'u8',
+    step_approvals: { array: { type: { array: { type: 'u8', len: 32 } } } },
+    auto_close: 'bool',
+    refund_allowlist: { array: { type: { array: { type: 'u8', len: 32 } } } },
+    evidence_uris: { array: { type: 'string' } },
+    notification_tag: 'string',
+    max_single_payout: 'u64',
+    rent_recipient: rentRecipientSchema,
+    delegated_increment: { option: { struct: {
+      delegate: { array: { type: 'u8', len: 32 } },
+      expiry: 'i64',
+    } } },
+    dual_increment: 'bool',
+    pending_signer_change: { option: { struct: {
+      authorized_releasers: { array: { type: { array: { type: 'u8', len: 32 } } } },
+      approvers: { array: { type: { array: { type: 'u8', len: 32 } } } },
+      timelock_expiry: 'i64',
+    } } },
+    breach_compensation_bps: 'u16',
+    step_recipients: { array: { type: { array: { type: 'u8', len: 32 } } } },
+    registry_program: { option: { array: { type: 'u8', len: 32 } } },
+    step_bps: { array: { type: 'u16' } },
+    confirmations_required: 'u8',
+    step_confirmers: 'u8',
+    allow_skip: 'bool',
+    paid_steps: 'u64',
+    pending_step: 'u64',
+    category: 'u8',
+    paid_order: { array: { type: 'u64' } },
+  }};
+
+  const createContractSchema = { struct: {
+    variant: 'u8', contract_id: 'string', total_quantity: 'u64',
+    notify_program: { option: { array: { type: 'u8', len: 32 } } },
+    rounding: 'u8',
+    payout_delay: 'i64',
+    authorized_releasers: { array: { type: { array: { type: 'u8', len: 32 } } } },
+    require_proof: 'bool',
+    approvers: { array: { type: { array: { type: 'u8', len: 32 } } } },
+    approval_threshold: 'u8',
+    auto_close: 'bool',
+    refund_allowlist: { array: { type: { array: { type: 'u8', len: 32 } } } },
+    notification_tag: 'string',
+    max_single_payout: 'u64',
+    rent_recipient: rentRecipientSchema,
+    dual_increment: 'bool',
+    breach_compensation_bps: 'u16',
+    step_recipients: { array: { type: { array: { type: 'u8', len: 32 } } } },
+    registry_program: { option: { array: { type: 'u8', len: 32 } } },
+    step_bps: { array: { type: 'u16' } },
+    confirmations_required: 'u8',
+    allow_skip: 'bool',
+    category: 'u8',
   }};
 
-  const instructionSchema = { struct: {
-    variant: 'u8', contract_id: 'string', total_quantity: 'u64'
+  const incrementStepSchema = { struct: {
+    variant: 'u8', contract_id: 'string',
+    expected_step: { option: 'u64' },
   }};
   
   const programId = new PublicKey("D1JKf9t3tEBzP7jES8bUzCQdLSYSqfcJ2S558AbQruJm");
@@ -32,11 +163,61 @@ import {
       owner: PublicKey,
       worker: PublicKey,
       contractId: String,
-      totalQuantity: Number
+      totalQuantity: Number,
+      notifyProgram: PublicKey | null = null,
+      rounding: RoundingPolicy = RoundingPolicy.FavorWorker,
+      payoutDelay: Number = 0,
+      authorizedReleasers: PublicKey[] = [],
+      requireProof: Boolean = false,
+      approvers: PublicKey[] = [],
+      approvalThreshold: Number = 0,
+      autoClose: Boolean = false,
+      refundAllowlist: PublicKey[] = [],
+      notificationTag: String = "",
+      maxSinglePayout: Number = 0,
+      rentTreasury: PublicKey | null = null,
+      dualIncrement: Boolean = false,
+      breachCompensationBps: Number = 0,
+      stepRecipients: PublicKey[] = [],
+      registryProgram: PublicKey | null = null,
+      stepBps: Number[] = [],
+      confirmationsRequired: Number = 0,
+      allowSkip: Boolean = false,
+      category: ContractCategory = ContractCategory.Milestone
   ): TransactionInstruction {
 
       const pda = getPda(owner, worker, contractId);
-      const instructionData = getIntructionData(0, contractId, totalQuantity);
+      const instructionData = getCreateContractData(
+        contractId,
+        totalQuantity,
+        notifyProgram,
+        rounding,
+        payoutDelay,
+        authorizedReleasers,
+        requireProof,
+        approvers,
+        approvalThreshold,
+        autoClose,
+        refundAllowlist,
+        notificationTag,
+        maxSinglePayout,
+        rentTreasury,
+        dualIncrement,
+        breachCompensationBps,
+        stepRecipients,
+        registryProgram,
+        stepBps,
+        confirmationsRequired,
+        allowSkip,
+        category
+      );
+      const registryKeys = registryProgram == null ? [] : [
+        {
+          pubkey: registryProgram,
+          isSigner: false,
+          isWritable: false
+        }
+      ];
   
       return new TransactionInstruction({
           keys: [
@@ -59,7 +240,8 @@ import {
               pubkey: SystemProgram.programId,
               isSigner: false,
               isWritable: false
-            }
+            },
+            ...registryKeys
           ],
           programId: programId,
           data: Buffer.from(instructionData)
@@ -69,11 +251,21 @@ import {
   export function incrementStep(
     owner: PublicKey,
     worker: PublicKey,
-    contractId: String
+    contractId: String,
+    notifyProgram: PublicKey | null = null,
+    expectedStep: Number | null = null,
+    workerSigns: Boolean = false
   ): TransactionInstruction {
   
       const pda = getPda(owner, worker, contractId);
-      const instructionData = getIntructionData(1, contractId, 0);
+      const instructionData = getIncrementStepData(contractId, expectedStep);
+      const notifyKeys = notifyProgram == null ? [] : [
+        {
+          pubkey: notifyProgram,
+          isSigner: false,
+          isWritable: false
+        }
+      ];
   
       return new TransactionInstruction({
           keys: [
@@ -84,14 +276,15 @@ import {
             },
             {
               pubkey: worker,
-              isSigner:false,
+              isSigner: workerSigns,
               isWritable: true
             },
             {
               pubkey: pda,
               isSigner: false,
               isWritable: true
-            }
+            },
+            ...notifyKeys
           ],
           programId: programId,
           data: Buffer.from(instructionData)
@@ -113,6 +306,7 @@ import {
             
             const owner: Uint8Array = data?.valueOf()["owner"];
             const worker: Uint8Array = data?.valueOf()["worker"];
+            const notifyProgram: Uint8Array | null = data?.valueOf()["notify_program"];
 
             return {
                 contractId: data?.valueOf()["contract_id"],
@@ -120,6 +314,67 @@ import {
                 worker: new PublicKey(worker),
                 totalQuantity: data?.valueOf()["total_quantity"],
                 actualStep: data?.valueOf()["actual_step"],
+                notifyProgram: notifyProgram == null ? null : new PublicKey(notifyProgram),
+                status: data?.valueOf()["status"],
+                rounding: data?.valueOf()["rounding"],
+                frozen: data?.valueOf()["frozen"],
+                payoutDelay: data?.valueOf()["payout_delay"],
+                pendingAmount: data?.valueOf()["pending_amount"],
+                pendingSince: data?.valueOf()["pending_since"],
+                memos: data?.valueOf()["memos"],
+                authorizedReleasers: data?.valueOf()["authorized_releasers"]
+                  .map((releaser: Uint8Array) => new PublicKey(releaser)),
+                lastActivityTs: data?.valueOf()["last_activity_ts"],
+                stepAmounts: data?.valueOf()["step_amounts"],
+                closeRequestedAt: data?.valueOf()["close_requested_at"],
+                requireProof: data?.valueOf()["require_proof"],
+                proofs: data?.valueOf()["proofs"],
+                totalDisbursed: data?.valueOf()["total_disbursed"],
+                bump: data?.valueOf()["bump"],
+                approvers: data?.valueOf()["approvers"]
+                  .map((approver: Uint8Array) => new PublicKey(approver)),
+                approvalThreshold: data?.valueOf()["approval_threshold"],
+                stepApprovals: data?.valueOf()["step_approvals"]
+                  .map((approver: Uint8Array) => new PublicKey(approver)),
+                autoClose: data?.valueOf()["auto_close"],
+                refundAllowlist: data?.valueOf()["refund_allowlist"]
+                  .map((destination: Uint8Array) => new PublicKey(destination)),
+                evidenceUris: data?.valueOf()["evidence_uris"],
+                notificationTag: data?.valueOf()["notification_tag"],
+                maxSinglePayout: data?.valueOf()["max_single_payout"],
+                rentTreasury: data?.valueOf()["rent_recipient"].treasury
+                  ? new PublicKey(data?.valueOf()["rent_recipient"].treasury)
+                  : null,
+                delegatedIncrement: data?.valueOf()["delegated_increment"]
+                  ? {
+                      delegate: new PublicKey(data?.valueOf()["delegated_increment"].delegate),
+                      expiry: data?.valueOf()["delegated_increment"].expiry,
+                    }
+                  : null,
+                dualIncrement: data?.valueOf()["dual_increment"],
+                pendingSignerChange: data?.valueOf()["pending_signer_change"]
+                  ? {
+                      authorizedReleasers: data?.valueOf()["pending_signer_change"].authorized_releasers
+                        .map((releaser: Uint8Array) => new PublicKey(releaser)),
+                      approvers: data?.valueOf()["pending_signer_change"].approvers
+                        .map((approver: Uint8Array) => new PublicKey(approver)),
+                      timelockExpiry: data?.valueOf()["pending_signer_change"].timelock_expiry,
+                    }
+                  : null,
+                breachCompensationBps: data?.valueOf()["breach_compensation_bps"],
+                stepRecipients: data?.valueOf()["step_recipients"]
+                  .map((recipient: Uint8Array) => new PublicKey(recipient)),
+                registryProgram: data?.valueOf()["registry_program"]
+                  ? new PublicKey(data?.valueOf()["registry_program"])
+                  : null,
+                stepBps: data?.valueOf()["step_bps"],
+                confirmationsRequired: data?.valueOf()["confirmations_required"],
+                stepConfirmers: data?.valueOf()["step_confirmers"],
+                allowSkip: data?.valueOf()["allow_skip"],
+                paidSteps: data?.valueOf()["paid_steps"],
+                pendingStep: data?.valueOf()["pending_step"],
+                category: data?.valueOf()["category"],
+                paidOrder: data?.valueOf()["paid_order"],
             };
         }
         
@@ -144,11 +399,74 @@ import {
     return pda;
   }
 
-  function getIntructionData(variant: Number, contractId: String, totalQuantity: Number)
+  function getCreateContractData(
+    contractId: String,
+    totalQuantity: Number,
+    notifyProgram: PublicKey | null = null,
+    rounding: RoundingPolicy = RoundingPolicy.FavorWorker,
+    payoutDelay: Number = 0,
+    authorizedReleasers: PublicKey[] = [],
+    requireProof: Boolean = false,
+    approvers: PublicKey[] = [],
+    approvalThreshold: Number = 0,
+    autoClose: Boolean = false,
+    refundAllowlist: PublicKey[] = [],
+    notificationTag: String = "",
+    maxSinglePayout: Number = 0,
+    rentTreasury: PublicKey | null = null,
+    dualIncrement: Boolean = false,
+    breachCompensationBps: Number = 0,
+    stepRecipients: PublicKey[] = [],
+    registryProgram: PublicKey | null = null,
+    stepBps: Number[] = [],
+    confirmationsRequired: Number = 0,
+    allowSkip: Boolean = false,
+    category: ContractCategory = ContractCategory.Milestone)
   {
     return borsh
         .serialize(
-            instructionSchema,
-            { variant: variant, contract_id: contractId, total_quantity: totalQuantity }
+            createContractSchema,
+            {
+              variant: 0,
+              contract_id: contractId,
+              total_quantity: totalQuantity,
+              notify_program: notifyProgram == null ? null : notifyProgram.toBytes(),
+              rounding: rounding,
+              payout_delay: payoutDelay,
+              authorized_releasers: authorizedReleasers.map(releaser => releaser.toBytes()),
+              require_proof: requireProof,
+              approvers: approvers.map(approver => approver.toBytes()),
+              approval_threshold: approvalThreshold,
+              auto_close: autoClose,
+              refund_allowlist: refundAllowlist.map(destination => destination.toBytes()),
+              notification_tag: notificationTag,
+              max_single_payout: maxSinglePayout,
+              rent_recipient: rentTreasury == null
+                ? { owner: {} }
+                : { treasury: rentTreasury.toBytes() },
+              dual_increment: dualIncrement,
+              breach_compensation_bps: breachCompensationBps,
+              step_recipients: stepRecipients.map(recipient => recipient.toBytes()),
+              registry_program: registryProgram == null ? null : registryProgram.toBytes(),
+              step_bps: stepBps,
+              confirmations_required: confirmationsRequired,
+              allow_skip: allowSkip,
+              category: category
+            }
+        );
+  }
+
+  function getIncrementStepData(
+    contractId: String,
+    expectedStep: Number | null = null)
+  {
+    return borsh
+        .serialize(
+            incrementStepSchema,
+            {
+              variant: 1,
+              contract_id: contractId,
+              expected_step: expectedStep
+            }
         );
   }
\ No newline at end of file
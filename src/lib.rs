@@ -4,23 +4,276 @@ use solana_program::{
     borsh1::try_from_slice_unchecked,
     entrypoint::ProgramResult,
     entrypoint,
+    instruction::AccountMeta,
     msg,
-    program::{invoke, invoke_signed},
+    program::{invoke, invoke_signed, set_return_data, MAX_RETURN_DATA},
     program_error::ProgramError,
-    pubkey::Pubkey,
+    pubkey::{Pubkey, MAX_SEED_LEN},
     system_instruction,
-    sysvar::{rent::Rent, Sysvar},
+    system_program,
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// `msg!` prefixed with the contract's notification tag, for the messages
+// reporting a change to the contract.
+macro_rules! contract_msg {
+    ($contract_data:expr, $($arg:tt)+) => {
+        msg!("{}{}", tag_prefix(&$contract_data.notification_tag), format!($($arg)+))
+    };
+}
+
+// The contract id is used as a PDA seed, so it can't be longer than a seed.
+pub const MAX_CONTRACT_ID_LEN: usize = MAX_SEED_LEN;
+
+pub const STEPS: u64 = 3;
+
+// Leads the archive seeds. Seeds are hashed back to back, so after the
+// contract id it would make archive "X" the very address of contract
+// "Xarchive".
+pub const ARCHIVE_SEED: &[u8] = b"archive";
+
+pub const MAX_MEMO_LEN: usize = 64;
+
+pub const MAX_NOTIFICATION_TAG_LEN: usize = 16;
+
+// Long enough for an `ipfs://` URI of a base32 CIDv1.
+pub const MAX_EVIDENCE_URI_LEN: usize = 96;
+
+pub const MAX_RELEASERS: usize = 5;
+
+pub const MAX_APPROVERS: usize = 5;
+
+pub const MAX_REFUND_DESTINATIONS: usize = 5;
+
+/// Seconds without any activity after which anyone can sweep a contract's
+/// escrow back to its owner, or to a destination on its refund allowlist.
+pub const DEAD_CONTRACT_TIMEOUT: i64 = 365 * 24 * 60 * 60;
+
+// Keeps the `GetClaimable` result well within the return data limit.
+pub const MAX_CLAIMABLE_CONTRACTS: usize = 16;
+
+// 15 `ContractState`s of 67 bytes fit the 1024 bytes of return data.
+pub const MAX_STATE_CONTRACTS: usize = 15;
+
+/// Maximum number of contracts one batch instruction, such as `BatchCancel`,
+/// processes. Batches are rejected above it before any contract is touched,
+/// so a batch never runs out of compute halfway, and 8 owner, worker and
+/// contract triples are well within the accounts a transaction can carry.
+pub const MAX_BATCH_SIZE: usize = 8;
+
+/// Bytes of the contract view each `Get` or `ExportSnapshot` page carries: the
+/// return data limit less the 32 bytes of the rest of a `ContractSnapshot`,
+/// the larger of the two results.
+pub const VIEW_PAGE_LEN: usize = MAX_RETURN_DATA - 32;
+
+/// Lamports of the escrow the final step may leave unrouted by the schedule.
+/// Anything more means some part of `total_quantity` was never assigned to a
+/// party. Lamports sent to the contract from outside don't count, they are
+/// refunded to the owner instead.
+pub const MAX_STRANDED_LAMPORTS: u64 = 0;
+
+/// Basis points in a whole, for the shares configured in basis points.
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+/// Cooling-off period between `RequestClose` and `ConfirmClose`.
+pub const CLOSE_DELAY_SECONDS: i64 = 24 * 60 * 60;
+
+/// Timelock between `ProposeSignerChange` and `ApplySignerChange`.
+pub const SIGNER_CHANGE_DELAY_SECONDS: i64 = 2 * 24 * 60 * 60;
+
+/// Bits of the `Verify` result, each set when the matching check passed.
+pub const VERIFY_PROGRAM_OWNED: u8 = 1 << 0;
+pub const VERIFY_DATA_READABLE: u8 = 1 << 1;
+pub const VERIFY_STEP_IN_RANGE: u8 = 1 << 2;
+pub const VERIFY_ESCROW_CONSISTENT: u8 = 1 << 3;
+pub const VERIFY_SEEDS_MATCH: u8 = 1 << 4;
+
+// Every instruction is its variant index as a single byte, followed by the
+// borsh encoding of the payload struct that variant reads. Trailing bytes are
+// rejected, so each variant only accepts its own payload.
+
+/// Payload of `CreateContract`, and of `ValidateCreate`, which checks the very
+/// instruction data a client is about to create a contract with.
 #[derive(BorshDeserialize)]
-pub struct InstructionPayload {
+pub struct CreatePayload {
     pub contract_id: String,
     pub total_quantity: u64,
+    pub notify_program: Option<Pubkey>,
+    pub rounding: RoundingPolicy,
+    pub payout_delay: i64,
+    pub authorized_releasers: Vec<Pubkey>,
+    pub require_proof: bool,
+    pub approvers: Vec<Pubkey>,
+    pub approval_threshold: u8,
+    pub auto_close: bool,
+    pub refund_allowlist: Vec<Pubkey>,
+    pub notification_tag: String,
+    pub max_single_payout: u64,
+    pub rent_recipient: RentRecipient,
+    pub dual_increment: bool,
+    pub breach_compensation_bps: u16,
+    pub step_recipients: Vec<Pubkey>,
+    pub registry_program: Option<Pubkey>,
+    pub step_bps: Vec<u16>,
+    pub confirmations_required: u8,
+    pub allow_skip: bool,
+    pub category: ContractCategory,
+}
+
+/// Payload of `IncrementStep`.
+#[derive(BorshDeserialize)]
+pub struct IncrementPayload {
+    pub contract_id: String,
+    pub expected_step: Option<u64>,
+}
+
+/// Payload of every variant that only names the contract it acts on.
+#[derive(BorshDeserialize)]
+pub struct ContractIdPayload {
+    pub contract_id: String,
+}
+
+/// Payload of `IncrementStepWithMemo`.
+#[derive(BorshDeserialize)]
+pub struct MemoPayload {
+    pub contract_id: String,
+    pub memo: String,
+}
+
+/// Payload of `IncrementStepWithProof`.
+#[derive(BorshDeserialize)]
+pub struct ProofPayload {
+    pub contract_id: String,
+    pub proof: [u8; 32],
+    pub evidence_uri: String,
+}
+
+/// Payload of `IncrementSpecificStep`.
+#[derive(BorshDeserialize)]
+pub struct SpecificStepPayload {
+    pub contract_id: String,
+    pub step: u64,
+}
+
+/// Payload of `Get` and `ExportSnapshot`.
+#[derive(BorshDeserialize)]
+pub struct PagePayload {
+    pub contract_id: String,
+    pub page: u16,
+}
+
+/// Payload of `Rollover`.
+#[derive(BorshDeserialize)]
+pub struct RolloverPayload {
+    pub old_contract_id: String,
+    pub new_contract_id: String,
+}
+
+/// Payload of `AddReleaser` and `RemoveReleaser`.
+#[derive(BorshDeserialize)]
+pub struct ReleaserPayload {
+    pub contract_id: String,
+    pub releaser: Pubkey,
+}
+
+/// Payload of `DelegateIncrement`.
+#[derive(BorshDeserialize)]
+pub struct DelegateIncrementPayload {
+    pub contract_id: String,
+    pub delegate: Pubkey,
+    pub expiry: i64,
+}
+
+/// Payload of `BatchCancel`.
+#[derive(BorshDeserialize)]
+pub struct BatchCancelPayload {
+    pub contract_ids: Vec<String>,
+}
+
+/// Payload of `ProposeSignerChange`.
+#[derive(BorshDeserialize)]
+pub struct SignerChangePayload {
+    pub contract_id: String,
+    pub authorized_releasers: Vec<Pubkey>,
+    pub approvers: Vec<Pubkey>,
+}
+
+/// Payload of `ReduceScope`.
+#[derive(BorshDeserialize)]
+pub struct ReduceScopePayload {
+    pub contract_id: String,
+    pub new_total: u64,
 }
 
+// Instructions are unpacked once per transaction and never stored, so the
+// size of `CreateContract` isn't worth boxing it for.
+#[allow(clippy::large_enum_variant)]
 pub enum Instruction {
-    CreateContract { contract_id: String, total_quantity: u64 },
-    IncrementStep { contract_id: String },
+    CreateContract {
+        contract_id: String,
+        total_quantity: u64,
+        notify_program: Option<Pubkey>,
+        rounding: RoundingPolicy,
+        payout_delay: i64,
+        authorized_releasers: Vec<Pubkey>,
+        require_proof: bool,
+        approvers: Vec<Pubkey>,
+        approval_threshold: u8,
+        auto_close: bool,
+        refund_allowlist: Vec<Pubkey>,
+        notification_tag: String,
+        max_single_payout: u64,
+        rent_recipient: RentRecipient,
+        dual_increment: bool,
+        breach_compensation_bps: u16,
+        step_recipients: Vec<Pubkey>,
+        registry_program: Option<Pubkey>,
+        step_bps: Vec<u16>,
+        confirmations_required: u8,
+        allow_skip: bool,
+        category: ContractCategory,
+    },
+    IncrementStep { contract_id: String, expected_step: Option<u64> },
+    Ping,
+    ValidateCreate { payload: CreatePayload },
+    RefundUnearned { contract_id: String },
+    Get { contract_id: String, page: u16 },
+    FreezeNewSteps { contract_id: String },
+    UnfreezeNewSteps { contract_id: String },
+    Archive { contract_id: String },
+    ReleasePending { contract_id: String },
+    ClawbackPending { contract_id: String },
+    RecoverStuck { contract_id: String },
+    IncrementStepWithMemo { contract_id: String, memo: String },
+    AddReleaser { contract_id: String, releaser: Pubkey },
+    RemoveReleaser { contract_id: String, releaser: Pubkey },
+    SweepAbandoned { contract_id: String },
+    ReduceScope { contract_id: String, new_total: u64 },
+    GetClaimable,
+    RequestClose { contract_id: String },
+    CancelCloseRequest { contract_id: String },
+    ConfirmClose { contract_id: String },
+    IncrementStepWithProof { contract_id: String, proof: [u8; 32], evidence_uri: String },
+    ApproveStep { contract_id: String },
+    Verify { contract_id: String },
+    Rollover { old_contract_id: String, new_contract_id: String },
+    CloseContract { contract_id: String },
+    QuoteIncrement { contract_id: String },
+    GetStates,
+    DelegateIncrement { contract_id: String, delegate: Pubkey, expiry: i64 },
+    Exists { contract_id: String },
+    BatchCancel { contract_ids: Vec<String> },
+    ProposeSignerChange { contract_id: String, authorized_releasers: Vec<Pubkey>, approvers: Vec<Pubkey> },
+    ApplySignerChange { contract_id: String },
+    CancelSignerChange { contract_id: String },
+    GetLimits,
+    GetProofs { contract_id: String },
+    ProjectRemaining { contract_id: String },
+    RecordConfirmation { contract_id: String },
+    ExportSnapshot { contract_id: String, page: u16 },
+    IncrementSpecificStep { contract_id: String, step: u64 },
 }
 
 impl Instruction {
@@ -29,23 +282,515 @@ impl Instruction {
             .split_first()
             .ok_or(ProgramError::InvalidInstructionData)?;
 
-        let payload = InstructionPayload::try_from_slice(rest).unwrap();
-
         match variant {
-            0 => Ok(
-                Self::CreateContract {
-                    contract_id: payload.contract_id,
-                    total_quantity: payload.total_quantity,
-                }
-            ),
-            1 => Ok(
-                Self::IncrementStep { contract_id: payload.contract_id }
-            ),
-            _ => return Err(ProgramError::InvalidInstructionData),
+            0 => {
+                let payload = parse_payload::<CreatePayload>(rest)?;
+                Ok(
+                    Self::CreateContract {
+                        contract_id: payload.contract_id,
+                        total_quantity: payload.total_quantity,
+                        notify_program: payload.notify_program,
+                        rounding: payload.rounding,
+                        payout_delay: payload.payout_delay,
+                        authorized_releasers: payload.authorized_releasers,
+                        require_proof: payload.require_proof,
+                        approvers: payload.approvers,
+                        approval_threshold: payload.approval_threshold,
+                        auto_close: payload.auto_close,
+                        refund_allowlist: payload.refund_allowlist,
+                        notification_tag: payload.notification_tag,
+                        max_single_payout: payload.max_single_payout,
+                        rent_recipient: payload.rent_recipient,
+                        dual_increment: payload.dual_increment,
+                        breach_compensation_bps: payload.breach_compensation_bps,
+                        step_recipients: payload.step_recipients,
+                        registry_program: payload.registry_program,
+                        step_bps: payload.step_bps,
+                        confirmations_required: payload.confirmations_required,
+                        allow_skip: payload.allow_skip,
+                        category: payload.category,
+                    }
+                )
+            }
+            1 => {
+                let payload = parse_payload::<IncrementPayload>(rest)?;
+                Ok(
+                    Self::IncrementStep {
+                        contract_id: payload.contract_id,
+                        expected_step: payload.expected_step,
+                    }
+                )
+            }
+            2 => {
+                parse_payload::<()>(rest)?;
+                Ok(Self::Ping)
+            }
+            3 => {
+                let payload = parse_payload::<CreatePayload>(rest)?;
+                Ok(Self::ValidateCreate { payload })
+            }
+            4 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::RefundUnearned { contract_id: payload.contract_id }
+                )
+            }
+            5 => {
+                let payload = parse_payload::<PagePayload>(rest)?;
+                Ok(
+                    Self::Get { contract_id: payload.contract_id, page: payload.page }
+                )
+            }
+            6 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::FreezeNewSteps { contract_id: payload.contract_id }
+                )
+            }
+            7 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::UnfreezeNewSteps { contract_id: payload.contract_id }
+                )
+            }
+            8 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::Archive { contract_id: payload.contract_id }
+                )
+            }
+            9 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::ReleasePending { contract_id: payload.contract_id }
+                )
+            }
+            10 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::ClawbackPending { contract_id: payload.contract_id }
+                )
+            }
+            11 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::RecoverStuck { contract_id: payload.contract_id }
+                )
+            }
+            12 => {
+                let payload = parse_payload::<MemoPayload>(rest)?;
+                Ok(
+                    Self::IncrementStepWithMemo {
+                        contract_id: payload.contract_id,
+                        memo: payload.memo,
+                    }
+                )
+            }
+            13 => {
+                let payload = parse_payload::<ReleaserPayload>(rest)?;
+                Ok(
+                    Self::AddReleaser {
+                        contract_id: payload.contract_id,
+                        releaser: payload.releaser,
+                    }
+                )
+            }
+            14 => {
+                let payload = parse_payload::<ReleaserPayload>(rest)?;
+                Ok(
+                    Self::RemoveReleaser {
+                        contract_id: payload.contract_id,
+                        releaser: payload.releaser,
+                    }
+                )
+            }
+            15 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::SweepAbandoned { contract_id: payload.contract_id }
+                )
+            }
+            16 => {
+                let payload = parse_payload::<ReduceScopePayload>(rest)?;
+                Ok(
+                    Self::ReduceScope {
+                        contract_id: payload.contract_id,
+                        new_total: payload.new_total,
+                    }
+                )
+            }
+            17 => {
+                parse_payload::<()>(rest)?;
+                Ok(Self::GetClaimable)
+            }
+            18 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::RequestClose { contract_id: payload.contract_id }
+                )
+            }
+            19 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::CancelCloseRequest { contract_id: payload.contract_id }
+                )
+            }
+            20 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::ConfirmClose { contract_id: payload.contract_id }
+                )
+            }
+            21 => {
+                let payload = parse_payload::<ProofPayload>(rest)?;
+                Ok(
+                    Self::IncrementStepWithProof {
+                        contract_id: payload.contract_id,
+                        proof: payload.proof,
+                        evidence_uri: payload.evidence_uri,
+                    }
+                )
+            }
+            22 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::ApproveStep { contract_id: payload.contract_id }
+                )
+            }
+            23 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::Verify { contract_id: payload.contract_id }
+                )
+            }
+            24 => {
+                let payload = parse_payload::<RolloverPayload>(rest)?;
+                Ok(
+                    Self::Rollover {
+                        old_contract_id: payload.old_contract_id,
+                        new_contract_id: payload.new_contract_id,
+                    }
+                )
+            }
+            25 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::CloseContract { contract_id: payload.contract_id }
+                )
+            }
+            26 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::QuoteIncrement { contract_id: payload.contract_id }
+                )
+            }
+            27 => {
+                parse_payload::<()>(rest)?;
+                Ok(Self::GetStates)
+            }
+            28 => {
+                let payload = parse_payload::<DelegateIncrementPayload>(rest)?;
+                Ok(
+                    Self::DelegateIncrement {
+                        contract_id: payload.contract_id,
+                        delegate: payload.delegate,
+                        expiry: payload.expiry,
+                    }
+                )
+            }
+            29 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::Exists { contract_id: payload.contract_id }
+                )
+            }
+            30 => {
+                let payload = parse_payload::<BatchCancelPayload>(rest)?;
+                Ok(
+                    Self::BatchCancel { contract_ids: payload.contract_ids }
+                )
+            }
+            31 => {
+                let payload = parse_payload::<SignerChangePayload>(rest)?;
+                Ok(
+                    Self::ProposeSignerChange {
+                        contract_id: payload.contract_id,
+                        authorized_releasers: payload.authorized_releasers,
+                        approvers: payload.approvers,
+                    }
+                )
+            }
+            32 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::ApplySignerChange { contract_id: payload.contract_id }
+                )
+            }
+            33 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::CancelSignerChange { contract_id: payload.contract_id }
+                )
+            }
+            34 => {
+                parse_payload::<()>(rest)?;
+                Ok(Self::GetLimits)
+            }
+            35 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::GetProofs { contract_id: payload.contract_id }
+                )
+            }
+            36 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::ProjectRemaining { contract_id: payload.contract_id }
+                )
+            }
+            37 => {
+                let payload = parse_payload::<ContractIdPayload>(rest)?;
+                Ok(
+                    Self::RecordConfirmation { contract_id: payload.contract_id }
+                )
+            }
+            38 => {
+                let payload = parse_payload::<PagePayload>(rest)?;
+                Ok(
+                    Self::ExportSnapshot { contract_id: payload.contract_id, page: payload.page }
+                )
+            }
+            39 => {
+                let payload = parse_payload::<SpecificStepPayload>(rest)?;
+                Ok(
+                    Self::IncrementSpecificStep { contract_id: payload.contract_id, step: payload.step }
+                )
+            }
+            _ => Err(ProgramError::InvalidInstructionData),
         }
     }
 }
 
+// A payload that is short, malformed or followed by extra bytes is invalid
+// instruction data, never a panic.
+fn parse_payload<T: BorshDeserialize>(rest: &[u8]) -> Result<T, ProgramError> {
+    T::try_from_slice(rest).map_err(|_| ProgramError::InvalidInstructionData)
+}
+
+/// Account metas, in the order the handler reads them, for `ix`. Optional
+/// trailing accounts that depend on the stored contract (such as the
+/// `notify_program` or a releaser or delegate signing `IncrementStep` in place
+/// of the owner), the approver signing `ApproveStep` or `RecordConfirmation`,
+/// a refund destination other than the owner, the treasury taking the rent of
+/// a closed contract, the recipient of a step paid to someone other than the
+/// worker, the `registry_program` of a created contract, and the contract
+/// accounts queried by `GetClaimable` or `GetStates`, are left for the caller
+/// to append.
+pub fn required_accounts(
+    ix: &Instruction,
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    worker: &Pubkey
+) -> Vec<AccountMeta> {
+    let pda = |contract_id: &str| find_contract_address(program_id, owner, worker, contract_id).0;
+
+    match ix {
+        Instruction::CreateContract { contract_id, .. } => vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(*worker, false),
+            AccountMeta::new(pda(contract_id), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        Instruction::IncrementStep { contract_id, .. }
+        | Instruction::IncrementStepWithMemo { contract_id, .. }
+        | Instruction::IncrementStepWithProof { contract_id, .. }
+        | Instruction::IncrementSpecificStep { contract_id, .. } => vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(*worker, false),
+            AccountMeta::new(pda(contract_id), false),
+        ],
+        Instruction::Ping => vec![],
+        Instruction::GetLimits => vec![],
+        Instruction::GetStates => vec![],
+        Instruction::BatchCancel { contract_ids } => contract_ids
+            .iter()
+            .flat_map(|contract_id| [
+                AccountMeta::new(*owner, true),
+                AccountMeta::new_readonly(*worker, false),
+                AccountMeta::new(pda(contract_id), false),
+            ])
+            .collect(),
+        Instruction::GetClaimable => vec![
+            AccountMeta::new_readonly(*worker, false),
+        ],
+        Instruction::ValidateCreate { payload } => vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new_readonly(*worker, false),
+            AccountMeta::new_readonly(pda(&payload.contract_id), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        Instruction::RefundUnearned { contract_id }
+        | Instruction::ClawbackPending { contract_id }
+        | Instruction::ConfirmClose { contract_id }
+        | Instruction::CloseContract { contract_id } => vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(*worker, false),
+            AccountMeta::new(pda(contract_id), false),
+        ],
+        Instruction::Get { contract_id, .. }
+        | Instruction::Verify { contract_id }
+        | Instruction::QuoteIncrement { contract_id }
+        | Instruction::Exists { contract_id }
+        | Instruction::GetProofs { contract_id }
+        | Instruction::ProjectRemaining { contract_id }
+        | Instruction::ExportSnapshot { contract_id, .. } => vec![
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new_readonly(*worker, false),
+            AccountMeta::new_readonly(pda(contract_id), false),
+        ],
+        Instruction::Rollover { old_contract_id, new_contract_id } => vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(*worker, false),
+            AccountMeta::new(pda(old_contract_id), false),
+            AccountMeta::new(pda(new_contract_id), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        Instruction::ApproveStep { contract_id }
+        | Instruction::RecordConfirmation { contract_id } => vec![
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new_readonly(*worker, false),
+            AccountMeta::new(pda(contract_id), false),
+        ],
+        Instruction::FreezeNewSteps { contract_id }
+        | Instruction::UnfreezeNewSteps { contract_id }
+        | Instruction::AddReleaser { contract_id, .. }
+        | Instruction::RemoveReleaser { contract_id, .. }
+        | Instruction::DelegateIncrement { contract_id, .. }
+        | Instruction::RequestClose { contract_id }
+        | Instruction::CancelCloseRequest { contract_id }
+        | Instruction::ProposeSignerChange { contract_id, .. }
+        | Instruction::ApplySignerChange { contract_id }
+        | Instruction::CancelSignerChange { contract_id } => vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new_readonly(*worker, false),
+            AccountMeta::new(pda(contract_id), false),
+        ],
+        Instruction::Archive { contract_id } => vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(*worker, false),
+            AccountMeta::new_readonly(pda(contract_id), false),
+            AccountMeta::new(find_archive_address(program_id, owner, worker, contract_id).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        Instruction::SweepAbandoned { contract_id } => vec![
+            AccountMeta::new(*owner, false),
+            AccountMeta::new(*worker, false),
+            AccountMeta::new(pda(contract_id), false),
+        ],
+        Instruction::ReduceScope { contract_id, .. } => vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(*worker, true),
+            AccountMeta::new(pda(contract_id), false),
+        ],
+        Instruction::ReleasePending { contract_id } => vec![
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new(*worker, false),
+            AccountMeta::new(pda(contract_id), false),
+        ],
+        Instruction::RecoverStuck { contract_id } => vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(*worker, false),
+            AccountMeta::new(pda(contract_id), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractError {
+    ContractIdTooLong,
+    ContractAlreadyExists,
+    NotifyProgramMismatch,
+    PdaNotWritable,
+    ContractCancelled,
+    AllStepsCompleted,
+    WorkerIsProgram,
+    StepsFrozen,
+    ContractNotFinished,
+    ArchiveAlreadyExists,
+    PayoutPending,
+    NoPendingPayout,
+    PayoutStillLocked,
+    PayoutDelayElapsed,
+    ContractNotStuck,
+    MemoTooLong,
+    TooManyReleasers,
+    ReleaserAlreadyAdded,
+    ReleaserNotFound,
+    ContractNotAbandoned,
+    ScopeNotReduced,
+    ScopeBelowDisbursed,
+    PdaMismatch,
+    AmountOverflow,
+    WorkerMismatch,
+    TooManyContracts,
+    CloseAlreadyRequested,
+    CloseNotRequested,
+    CloseStillLocked,
+    ProofRequired,
+    InvalidProof,
+    InsufficientEscrow,
+    TooManyApprovers,
+    InvalidApprovalThreshold,
+    ApproverNotFound,
+    AlreadyApproved,
+    ApprovalsPending,
+    UnexpectedStep,
+    ApproverIsParty,
+    TooManyRefundDestinations,
+    RefundDestinationNotAllowed,
+    WrongOwner,
+    EvidenceUriTooLong,
+    EvidenceUriRequired,
+    StrandedLamports,
+    NotificationTagTooLong,
+    PayoutExceedsCap,
+    DelegationExpired,
+    WorkerAccountNotFunded,
+    SignerChangeAlreadyProposed,
+    SignerChangeNotProposed,
+    SignerChangeStillLocked,
+    CorruptData,
+    InvalidCompensationBps,
+    BatchTooLarge,
+    InvalidStepRecipients,
+    AccountTooSmall,
+    RegistryProgramMismatch,
+    InvalidStepBps,
+    InvalidConfirmationsRequired,
+    ConfirmationsPending,
+    InvalidViewPage,
+    SkipNotAllowed,
+    StepAlreadyPaid,
+    EscrowShortfall,
+    AlreadyConfirmed,
+}
+
+impl From<ContractError> for ProgramError {
+    fn from(error: ContractError) -> Self {
+        ProgramError::Custom(error as u32)
+    }
+}
+
+/// Releaser and approver sets proposed to replace the contract's, and the unix
+/// timestamp from which they can be applied.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct SignerChange {
+    pub authorized_releasers: Vec<Pubkey>,
+    pub approvers: Vec<Pubkey>,
+    pub timelock_expiry: i64,
+}
+
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct ContractData {
     pub contract_id: String,
@@ -53,16 +798,445 @@ pub struct ContractData {
     pub worker: Pubkey,
     pub total_quantity: u64,
     pub actual_step: u64,
+    pub notify_program: Option<Pubkey>,
+    pub status: ContractStatus,
+    pub rounding: RoundingPolicy,
+    pub frozen: bool,
+    /// Seconds a step payout is held in the contract before it can be
+    /// released to the worker. Zero pays the worker on increment.
+    pub payout_delay: i64,
+    pub pending_amount: u64,
+    pub pending_since: i64,
+    /// One memo per paid step, empty for steps paid without one.
+    pub memos: Vec<String>,
+    /// Accounts that may sign `IncrementStep` in place of the owner.
+    pub authorized_releasers: Vec<Pubkey>,
+    pub last_activity_ts: i64,
+    /// Worker payout for every step, built from `total_quantity` and
+    /// `rounding` at creation and rebuilt for the unpaid steps when the scope
+    /// changes.
+    pub step_amounts: Vec<u64>,
+    /// When the owner asked to close the contract, zero if no close is
+    /// pending.
+    pub close_requested_at: i64,
+    /// Steps may only be paid through `IncrementStepWithProof`.
+    pub require_proof: bool,
+    /// One proof hash per paid step, zeroed for steps paid without one.
+    pub proofs: Vec<[u8; 32]>,
+    /// Lamports that have left the escrow for paid steps, whether released to
+    /// the worker or clawed back by the owner.
+    pub total_disbursed: u64,
+    /// Bump seed of the contract address, found once at creation.
+    pub bump: u8,
+    /// Accounts whose approvals count towards `approval_threshold`.
+    pub approvers: Vec<Pubkey>,
+    /// Distinct approvals a step needs before it can be paid, zero if steps
+    /// need none.
+    pub approval_threshold: u8,
+    /// Approvers who have approved the current step so far.
+    pub step_approvals: Vec<Pubkey>,
+    /// Close the contract into the owner as soon as its final step is paid.
+    pub auto_close: bool,
+    /// Where refunds may be sent, only the owner if empty.
+    pub refund_allowlist: Vec<Pubkey>,
+    /// Where each step's deliverable can be found, empty for steps paid
+    /// without a proof.
+    pub evidence_uris: Vec<String>,
+    /// Prefixed to the messages logged for the contract, so its logs can be
+    /// found without an indexer.
+    pub notification_tag: String,
+    /// Largest payout a single step may make, zero for no cap.
+    pub max_single_payout: u64,
+    /// Who gets the rent reserve back when the contract is closed.
+    pub rent_recipient: RentRecipient,
+    /// A delegate allowed to sign the next increment in place of the owner,
+    /// and the unix timestamp after which it no longer can. Any increment
+    /// clears it, so it can never pay a later step than the one it was
+    /// signed for.
+    pub delegated_increment: Option<(Pubkey, i64)>,
+    /// Whether an increment needs both the owner and the worker to sign it,
+    /// neither releasers nor a delegate standing in for the owner.
+    pub dual_increment: bool,
+    /// Signer sets waiting for their timelock, set by `ProposeSignerChange`.
+    pub pending_signer_change: Option<SignerChange>,
+    /// Share, in basis points, of the unearned escrow paid to the worker when
+    /// the contract is swept as abandoned before all steps were paid.
+    pub breach_compensation_bps: u16,
+    /// Who each step pays, by step index, when the steps go to different
+    /// recipients. Empty when every step pays the worker.
+    pub step_recipients: Vec<Pubkey>,
+    /// Program told about the contract once it is created, for platforms that
+    /// keep a registry of their contracts.
+    pub registry_program: Option<Pubkey>,
+    /// Share of `total_quantity`, in basis points, paid by each step, when the
+    /// steps aren't split evenly. Empty for an even split.
+    pub step_bps: Vec<u16>,
+    /// Distinct approvers who have to confirm each step before it can be paid.
+    pub confirmations_required: u8,
+    /// One bit per approver, by index in `approvers`, set once they confirmed
+    /// the current step. `MAX_APPROVERS` keeps every index within the byte.
+    pub step_confirmers: u8,
+    /// Whether `IncrementSpecificStep` may pay the steps in any order.
+    pub allow_skip: bool,
+    /// One bit per step, set once the step is paid. Without skipping these
+    /// are always the lowest `actual_step` bits.
+    pub paid_steps: u64,
+    /// Step the held payout, if any, belongs to.
+    pub pending_step: u64,
+    /// Kind of work the contract pays for, for indexers to filter on.
+    pub category: ContractCategory,
+    /// Steps in the order they were paid, one for each entry of `memos`,
+    /// `proofs` and `evidence_uris`.
+    pub paid_order: Vec<u64>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractStatus {
+    Active,
+    Cancelled,
+}
+
+/// Where the rent reserve goes when the contract account is closed:
+/// - `Owner`: along with the rest of the refund, to the refund destination.
+/// - `Treasury`: to the given account, which the closing instruction has to
+///   include among its accounts.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RentRecipient {
+    Owner,
+    Treasury(Pubkey),
+}
+
+/// How the lamports left over by splitting the escrow into steps are handled
+/// when the schedule is built.
+/// - `FavorWorker`: every step is floored and the final step also pays the
+///   whole remainder to the worker.
+/// - `FavorOwner`: every step is floored and the remainder is refunded to the
+///   owner along with the final step.
+/// - `Banker`: each step pays the difference between the cumulative shares
+///   rounded half to even, spreading the remainder across the steps.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingPolicy {
+    FavorWorker,
+    FavorOwner,
+    Banker,
+}
+
+/// What kind of work a contract pays for, set at creation so indexers can
+/// filter contracts by it. It has no effect on how the contract behaves.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractCategory {
+    Milestone,
+    Bounty,
+    Retainer,
+}
+
+pub const CONTRACT_VIEW_VERSION: u8 = 31;
+
+/// Client-facing view of a contract returned, a page at a time, by `Get`.
+/// Unlike `ContractData` its layout is stable: fields are only ever appended,
+/// along with a bump of `version`.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ContractView {
+    pub version: u8,
+    pub contract_id: String,
+    pub owner: Pubkey,
+    pub worker: Pubkey,
+    pub total_quantity: u64,
+    pub actual_step: u64,
+    pub notify_program: Option<Pubkey>,
+    pub status: ContractStatus,
+    pub rounding: RoundingPolicy,
+    pub frozen: bool,
+    pub payout_delay: i64,
+    pub pending_amount: u64,
+    pub pending_since: i64,
+    pub memos: Vec<String>,
+    pub authorized_releasers: Vec<Pubkey>,
+    pub last_activity_ts: i64,
+    pub step_amounts: Vec<u64>,
+    pub close_requested_at: i64,
+    pub require_proof: bool,
+    pub proofs: Vec<[u8; 32]>,
+    pub total_disbursed: u64,
+    pub bump: u8,
+    pub approvers: Vec<Pubkey>,
+    pub approval_threshold: u8,
+    pub step_approvals: Vec<Pubkey>,
+    pub auto_close: bool,
+    pub refund_allowlist: Vec<Pubkey>,
+    pub evidence_uris: Vec<String>,
+    pub notification_tag: String,
+    pub max_single_payout: u64,
+    pub rent_recipient: RentRecipient,
+    pub delegated_increment: Option<(Pubkey, i64)>,
+    pub dual_increment: bool,
+    pub pending_signer_change: Option<SignerChange>,
+    pub breach_compensation_bps: u16,
+    pub step_recipients: Vec<Pubkey>,
+    pub registry_program: Option<Pubkey>,
+    pub step_bps: Vec<u16>,
+    pub confirmations_required: u8,
+    pub step_confirmers: u8,
+    pub allow_skip: bool,
+    pub paid_steps: u64,
+    pub pending_step: u64,
+    pub category: ContractCategory,
+    pub paid_order: Vec<u64>,
+}
+
+/// Result of `ExportSnapshot`, one page of a contract as of a given slot. The
+/// Borsh layout is, in order: `slot` as a little-endian u64, `unix_timestamp`
+/// as a little-endian i64, `lamports` (the contract account's whole balance,
+/// rent included) as a little-endian u64, `page` and `page_count` as
+/// little-endian u16s, then `contract` as a u32 length and that many bytes.
+/// Concatenated in page order, the `contract` bytes of every page are the
+/// contract's `ContractView`, starting with its `version`. A contract using
+/// most of its lists outgrows the return data, hence the pages, and pages
+/// taken at different slots don't belong together. The contract is exported
+/// as its view rather than as `ContractData` so the layout stays readable as
+/// fields are added. The runtime attributes return data to the program that
+/// set it, which is what vouches for the snapshot.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ContractSnapshot {
+    pub slot: u64,
+    pub unix_timestamp: i64,
+    pub lamports: u64,
+    pub page: u16,
+    pub page_count: u16,
+    pub contract: Vec<u8>,
+}
+
+/// Result of `Get`, one page of the contract's `ContractView`. Concatenated in
+/// page order, the `view` bytes of every page are the whole view, starting
+/// with its `version`. Pages read in separate transactions may straddle a
+/// change to the contract; `ExportSnapshot` pages carry the slot to tell.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ContractViewPage {
+    pub page: u16,
+    pub page_count: u16,
+    pub view: Vec<u8>,
+}
+
+impl From<ContractData> for ContractView {
+    fn from(contract_data: ContractData) -> Self {
+        Self {
+            version: CONTRACT_VIEW_VERSION,
+            contract_id: contract_data.contract_id,
+            owner: contract_data.owner,
+            worker: contract_data.worker,
+            total_quantity: contract_data.total_quantity,
+            actual_step: contract_data.actual_step,
+            notify_program: contract_data.notify_program,
+            status: contract_data.status,
+            rounding: contract_data.rounding,
+            frozen: contract_data.frozen,
+            payout_delay: contract_data.payout_delay,
+            pending_amount: contract_data.pending_amount,
+            pending_since: contract_data.pending_since,
+            memos: contract_data.memos,
+            authorized_releasers: contract_data.authorized_releasers,
+            last_activity_ts: contract_data.last_activity_ts,
+            step_amounts: contract_data.step_amounts,
+            close_requested_at: contract_data.close_requested_at,
+            require_proof: contract_data.require_proof,
+            proofs: contract_data.proofs,
+            total_disbursed: contract_data.total_disbursed,
+            bump: contract_data.bump,
+            approvers: contract_data.approvers,
+            approval_threshold: contract_data.approval_threshold,
+            step_approvals: contract_data.step_approvals,
+            auto_close: contract_data.auto_close,
+            refund_allowlist: contract_data.refund_allowlist,
+            evidence_uris: contract_data.evidence_uris,
+            notification_tag: contract_data.notification_tag,
+            max_single_payout: contract_data.max_single_payout,
+            rent_recipient: contract_data.rent_recipient,
+            delegated_increment: contract_data.delegated_increment,
+            dual_increment: contract_data.dual_increment,
+            pending_signer_change: contract_data.pending_signer_change,
+            breach_compensation_bps: contract_data.breach_compensation_bps,
+            step_recipients: contract_data.step_recipients,
+            registry_program: contract_data.registry_program,
+            step_bps: contract_data.step_bps,
+            confirmations_required: contract_data.confirmations_required,
+            step_confirmers: contract_data.step_confirmers,
+            allow_skip: contract_data.allow_skip,
+            paid_steps: contract_data.paid_steps,
+            pending_step: contract_data.pending_step,
+            category: contract_data.category,
+            paid_order: contract_data.paid_order,
+        }
+    }
+}
+
+/// Result of `QuoteIncrement`: the step the next increment pays, the payout
+/// the worker gets for it, the rounding remainder refunded to the owner with it
+/// and whether the payout is held, for `payout_delay` or until the worker's
+/// account is funded, rather than paid out.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct IncrementQuote {
+    pub step: u64,
+    pub worker_amount: u64,
+    pub owner_remainder: u64,
+    pub held: bool,
+}
+
+/// Result of `GetLimits`: the bounds the program enforces on what it is
+/// given, so that clients can check them before submitting.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ProgramLimits {
+    pub max_contract_id_len: u64,
+    pub steps: u64,
+    pub max_memo_len: u64,
+    pub max_notification_tag_len: u64,
+    pub max_evidence_uri_len: u64,
+    pub max_releasers: u64,
+    pub max_approvers: u64,
+    pub max_refund_destinations: u64,
+    pub max_claimable_contracts: u64,
+    pub max_state_contracts: u64,
+    pub max_batch_size: u64,
+    pub dead_contract_timeout: i64,
+    pub close_delay_seconds: i64,
+    pub signer_change_delay_seconds: i64,
+}
+
+/// One entry of the `GetProofs` result, what the increment of a paid step was
+/// submitted with, in step order. `proof` is all zeros for a step paid without
+/// one. At `STEPS` entries of at most 208 bytes the whole result fits the
+/// return data.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct StepRecord {
+    pub step: u64,
+    pub proof: [u8; 32],
+    pub memo: String,
+    pub evidence_uri: String,
+}
+
+/// One entry of the `ProjectRemaining` result: an unpaid step, what it pays
+/// the worker and the earliest unix timestamp that payout can reach them,
+/// given that each increment waits for the previous held payout.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ProjectedStep {
+    pub step: u64,
+    pub amount: u64,
+    pub earliest_timestamp: i64,
+}
+
+/// One entry of the `GetStates` result, a summary of `contract` small enough
+/// for many of them to fit in the return data.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ContractState {
+    pub contract: Pubkey,
+    pub actual_step: u64,
+    pub status: ContractStatus,
+    pub frozen: bool,
+    pub total_quantity: u64,
+    pub total_disbursed: u64,
+    pub pending_amount: u64,
+    pub category: ContractCategory,
+}
+
+/// One entry of the `GetClaimable` result: the payout `worker` can release
+/// from `contract` right now.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ClaimablePayout {
+    pub contract: Pubkey,
+    pub amount: u64,
+}
+
+/// Deliverable a step is paid against: its hash and where to find it.
+#[derive(Default)]
+pub struct StepProof {
+    pub hash: [u8; 32],
+    pub evidence_uri: String,
+}
+
+/// Payload sent to the contract's `registry_program` once it is created.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ContractRegistration {
+    pub contract: Pubkey,
+    pub owner: Pubkey,
+    pub worker: Pubkey,
+    pub contract_id: String,
+    pub total_quantity: u64,
+}
+
+/// Payload sent to the contract's `notify_program` after every paid step.
+/// `step` is the index of the step that was just paid, counted from zero.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct StepNotification {
+    pub contract_id: String,
+    pub step: u64,
+    pub amount: u64,
 }
 
 impl ContractData {
+    pub fn get_account_size(contract_id: &str) -> usize {
+        1
+        + 4
+        + contract_id.len()
+        + (2 * std::mem::size_of::<Pubkey>())
+        + (2 * std::mem::size_of::<u64>())
+        + (1 + std::mem::size_of::<Pubkey>())
+        + 1
+        + 1
+        + 1
+        + (2 * std::mem::size_of::<i64>())
+        + std::mem::size_of::<u64>()
+        // Room for a full-length memo on every step, so increments never
+        // need to grow the account.
+        + 4
+        + (STEPS as usize * (4 + MAX_MEMO_LEN))
+        + 4
+        + (MAX_RELEASERS * std::mem::size_of::<Pubkey>())
+        + std::mem::size_of::<i64>()
+        + 4
+        + (STEPS as usize * std::mem::size_of::<u64>())
+        + std::mem::size_of::<i64>()
+        + 1
+        + 4
+        + (STEPS as usize * 32)
+        + std::mem::size_of::<u64>()
+        + 1
+        + 4
+        + (MAX_APPROVERS * std::mem::size_of::<Pubkey>())
+        + 1
+        + 4
+        + (MAX_APPROVERS * std::mem::size_of::<Pubkey>())
+        + 1
+        + 4
+        + (MAX_REFUND_DESTINATIONS * std::mem::size_of::<Pubkey>())
+        + 4
+        + (STEPS as usize * (4 + MAX_EVIDENCE_URI_LEN))
+        + 4
+        + MAX_NOTIFICATION_TAG_LEN
+        + std::mem::size_of::<u64>()
+        + (1 + std::mem::size_of::<Pubkey>())
+        + (1 + std::mem::size_of::<Pubkey>() + std::mem::size_of::<i64>())
+        + 1
+        + (1
+            + (4 + MAX_RELEASERS * std::mem::size_of::<Pubkey>())
+            + (4 + MAX_APPROVERS * std::mem::size_of::<Pubkey>())
+            + std::mem::size_of::<i64>())
+        + std::mem::size_of::<u16>()
+        + 4
+        + (STEPS as usize * std::mem::size_of::<Pubkey>())
+        + (1 + std::mem::size_of::<Pubkey>())
+        + 4
+        + (STEPS as usize * std::mem::size_of::<u16>())
+        + 1
+        + 1
+        + 1
+        + std::mem::size_of::<u64>()
+        + std::mem::size_of::<u64>()
+        + 1
+        + 4
+        + (STEPS as usize * std::mem::size_of::<u64>())
+    }
+
     pub fn get_account_size_and_rent(contract_id: String) -> Result<(usize, u64), ProgramError> {
-        let account_len =
-            1
-            + 4
-            + contract_id.len()
-            + (2 * std::mem::size_of::<Pubkey>())
-            + (2 * std::mem::size_of::<u64>());
+        let account_len = Self::get_account_size(&contract_id);
 
         let rent = Rent::get()?;
         let rent_lamports = rent.minimum_balance(account_len);
@@ -81,20 +1255,197 @@ pub fn process_instruction(
     let instruction = Instruction::unpack(instruction_data)?;
 
     match instruction {
-        Instruction::CreateContract { contract_id, total_quantity } => {
-            create_contract_handler(program_id, accounts, contract_id, total_quantity)
+        Instruction::CreateContract {
+            contract_id,
+            total_quantity,
+            notify_program,
+            rounding,
+            payout_delay,
+            authorized_releasers,
+            require_proof,
+            approvers,
+            approval_threshold,
+            auto_close,
+            refund_allowlist,
+            notification_tag,
+            max_single_payout,
+            rent_recipient,
+            dual_increment,
+            breach_compensation_bps,
+            step_recipients,
+            registry_program,
+            step_bps,
+            confirmations_required,
+            allow_skip,
+            category,
+        } => {
+            create_contract_handler(
+                program_id,
+                accounts,
+                contract_id,
+                total_quantity,
+                notify_program,
+                rounding,
+                payout_delay,
+                authorized_releasers,
+                require_proof,
+                approvers,
+                approval_threshold,
+                auto_close,
+                refund_allowlist,
+                notification_tag,
+                max_single_payout,
+                rent_recipient,
+                dual_increment,
+                breach_compensation_bps,
+                step_recipients,
+                registry_program,
+                step_bps,
+                confirmations_required,
+                allow_skip,
+                category
+            )
+        }
+        Instruction::IncrementStep { contract_id, expected_step } => {
+            increment_step_handler(program_id, accounts, contract_id, String::new(), None, expected_step, None)
+        }
+        Instruction::Ping => ping(),
+        Instruction::GetLimits => get_limits(),
+        Instruction::GetProofs { contract_id } => {
+            get_proofs_handler(program_id, accounts, contract_id)
+        }
+        Instruction::ProjectRemaining { contract_id } => {
+            project_remaining_handler(program_id, accounts, contract_id)
+        }
+        Instruction::RecordConfirmation { contract_id } => {
+            record_confirmation_handler(program_id, accounts, contract_id)
+        }
+        Instruction::ExportSnapshot { contract_id, page } => {
+            export_snapshot_handler(program_id, accounts, contract_id, page)
+        }
+        Instruction::IncrementSpecificStep { contract_id, step } => {
+            increment_step_handler(program_id, accounts, contract_id, String::new(), None, None, Some(step))
+        }
+        Instruction::ValidateCreate { payload } => {
+            validate_create_handler(program_id, accounts, payload)
+        }
+        Instruction::RefundUnearned { contract_id } => {
+            refund_unearned_handler(program_id, accounts, contract_id)
+        }
+        Instruction::Get { contract_id, page } => {
+            get_handler(program_id, accounts, contract_id, page)
+        }
+        Instruction::FreezeNewSteps { contract_id } => {
+            set_frozen_handler(program_id, accounts, contract_id, true)
+        }
+        Instruction::UnfreezeNewSteps { contract_id } => {
+            set_frozen_handler(program_id, accounts, contract_id, false)
+        }
+        Instruction::Archive { contract_id } => {
+            archive_handler(program_id, accounts, contract_id)
+        }
+        Instruction::ReleasePending { contract_id } => {
+            release_pending_handler(program_id, accounts, contract_id)
+        }
+        Instruction::ClawbackPending { contract_id } => {
+            clawback_pending_handler(program_id, accounts, contract_id)
+        }
+        Instruction::RecoverStuck { contract_id } => {
+            recover_stuck_handler(program_id, accounts, contract_id)
+        }
+        Instruction::IncrementStepWithMemo { contract_id, memo } => {
+            increment_step_handler(program_id, accounts, contract_id, memo, None, None, None)
+        }
+        Instruction::AddReleaser { contract_id, releaser } => {
+            update_releasers_handler(program_id, accounts, contract_id, releaser, true)
+        }
+        Instruction::RemoveReleaser { contract_id, releaser } => {
+            update_releasers_handler(program_id, accounts, contract_id, releaser, false)
+        }
+        Instruction::SweepAbandoned { contract_id } => {
+            sweep_abandoned_handler(program_id, accounts, contract_id)
+        }
+        Instruction::ReduceScope { contract_id, new_total } => {
+            reduce_scope_handler(program_id, accounts, contract_id, new_total)
+        }
+        Instruction::GetClaimable => get_claimable_handler(program_id, accounts),
+        Instruction::GetStates => get_states_handler(program_id, accounts),
+        Instruction::RequestClose { contract_id } => {
+            request_close_handler(program_id, accounts, contract_id, true)
+        }
+        Instruction::CancelCloseRequest { contract_id } => {
+            request_close_handler(program_id, accounts, contract_id, false)
+        }
+        Instruction::ConfirmClose { contract_id } => {
+            confirm_close_handler(program_id, accounts, contract_id)
+        }
+        Instruction::IncrementStepWithProof { contract_id, proof, evidence_uri } => {
+            let proof = StepProof { hash: proof, evidence_uri };
+
+            increment_step_handler(program_id, accounts, contract_id, String::new(), Some(proof), None, None)
+        }
+        Instruction::ApproveStep { contract_id } => {
+            approve_step_handler(program_id, accounts, contract_id)
+        }
+        Instruction::Verify { contract_id } => {
+            verify_handler(program_id, accounts, contract_id)
+        }
+        Instruction::Exists { contract_id } => {
+            exists_handler(program_id, accounts, contract_id)
+        }
+        Instruction::BatchCancel { contract_ids } => {
+            batch_cancel_handler(program_id, accounts, contract_ids)
+        }
+        Instruction::ProposeSignerChange { contract_id, authorized_releasers, approvers } => {
+            propose_signer_change_handler(program_id, accounts, contract_id, authorized_releasers, approvers)
         }
-        Instruction::IncrementStep { contract_id } => {
-            increment_step_handler(program_id, accounts, contract_id)
+        Instruction::ApplySignerChange { contract_id } => {
+            signer_change_handler(program_id, accounts, contract_id, true)
+        }
+        Instruction::CancelSignerChange { contract_id } => {
+            signer_change_handler(program_id, accounts, contract_id, false)
+        }
+        Instruction::Rollover { old_contract_id, new_contract_id } => {
+            rollover_handler(program_id, accounts, old_contract_id, new_contract_id)
+        }
+        Instruction::CloseContract { contract_id } => {
+            close_contract_handler(program_id, accounts, contract_id)
+        }
+        Instruction::QuoteIncrement { contract_id } => {
+            quote_increment_handler(program_id, accounts, contract_id)
+        }
+        Instruction::DelegateIncrement { contract_id, delegate, expiry } => {
+            delegate_increment_handler(program_id, accounts, contract_id, delegate, expiry)
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_contract_handler(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     contract_id: String,
     total_quantity: u64,
+    notify_program: Option<Pubkey>,
+    rounding: RoundingPolicy,
+    payout_delay: i64,
+    authorized_releasers: Vec<Pubkey>,
+    require_proof: bool,
+    approvers: Vec<Pubkey>,
+    approval_threshold: u8,
+    auto_close: bool,
+    refund_allowlist: Vec<Pubkey>,
+    notification_tag: String,
+    max_single_payout: u64,
+    rent_recipient: RentRecipient,
+    dual_increment: bool,
+    breach_compensation_bps: u16,
+    step_recipients: Vec<Pubkey>,
+    registry_program: Option<Pubkey>,
+    step_bps: Vec<u16>,
+    confirmations_required: u8,
+    allow_skip: bool,
+    category: ContractCategory,
 ) -> ProgramResult {
 
     let account_info_iter = &mut accounts.iter();
@@ -104,20 +1455,25 @@ fn create_contract_handler(
     let pda = next_account_info(account_info_iter)?;
     let system_program = next_account_info(account_info_iter)?;
 
-    if total_quantity > owner.lamports() {
-        return Err(ProgramError::InsufficientFunds);
-    }
+    validate_system_program(system_program)?;
 
-    let (pda_key, bump_seed) = Pubkey::find_program_address(
-        &[
-            owner.key.as_ref(),
-            worker.key.as_ref(),
-            contract_id.as_bytes().as_ref(),
-        ],
-        program_id,
-    );
+    let bump_seed =
+        validate_create_contract(program_id, owner, worker, pda, &contract_id, total_quantity)?;
 
-    validate_accounts_on_creation(owner, pda, &pda_key)?;
+    validate_create_terms(
+        owner.key,
+        worker.key,
+        payout_delay,
+        &authorized_releasers,
+        &approvers,
+        approval_threshold,
+        &refund_allowlist,
+        &notification_tag,
+        breach_compensation_bps,
+        &step_recipients,
+        &step_bps,
+        confirmations_required
+    )?;
 
     create_contract(
         program_id,
@@ -125,159 +1481,4339 @@ fn create_contract_handler(
         worker,
         pda,
         system_program,
+        account_info_iter.next(),
         bump_seed,
         contract_id,
-        total_quantity
+        total_quantity,
+        notify_program,
+        rounding,
+        payout_delay,
+        authorized_releasers,
+        require_proof,
+        approvers,
+        approval_threshold,
+        auto_close,
+        refund_allowlist,
+        notification_tag,
+        max_single_payout,
+        rent_recipient,
+        dual_increment,
+        breach_compensation_bps,
+        step_recipients,
+        registry_program,
+        step_bps,
+        confirmations_required,
+        allow_skip,
+        category
     )
 }
 
-fn create_contract<'a>(
+fn validate_create_handler(
     program_id: &Pubkey,
-    owner: &AccountInfo<'a>,
-    worker: &AccountInfo<'a>,
-    pda: &AccountInfo<'a>,
-    system_program: &AccountInfo<'a>,
-    bump_seed: u8,
-    contract_id: String,
-    total_quantity: u64
+    accounts: &[AccountInfo],
+    payload: CreatePayload,
 ) -> ProgramResult {
 
-    let (account_len, rent_lamports) =
-        ContractData::get_account_size_and_rent(contract_id.clone())?;
-
-    invoke_signed(
-        &system_instruction::create_account(
-            owner.key,
-            pda.key,
-            rent_lamports,
-            account_len.try_into().unwrap(),
-            program_id,
-        ),
-        &[owner.clone(), pda.clone(), system_program.clone()],
-        &[&[
-            owner.key.as_ref(),
-            worker.key.as_ref(),
-            contract_id.as_bytes().as_ref(),
-            &[bump_seed],
-        ]],
-    )?;
-
-    invoke(
-        &system_instruction::transfer(owner.key, pda.key, total_quantity),
-        &[owner.clone(), pda.clone(), system_program.clone()],
-    )?;
+    let account_info_iter = &mut accounts.iter();
 
-    let mut contract_data =
-        try_from_slice_unchecked::<ContractData>(&pda.data.borrow())?;
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
 
-    contract_data.contract_id = contract_id;
-    contract_data.owner = owner.key.clone();
-    contract_data.worker = worker.key.clone();
-    contract_data.total_quantity = total_quantity;
-    contract_data.actual_step = 0;
+    // The same guards, in the same order, as `create_contract_handler`, so
+    // the code reported is the one creating the contract would fail with.
+    let validation = validate_system_program(system_program)
+        .and_then(|_| validate_create_contract(
+            program_id,
+            owner,
+            worker,
+            pda,
+            &payload.contract_id,
+            payload.total_quantity
+        ))
+        .and_then(|_| validate_create_terms(
+            owner.key,
+            worker.key,
+            payload.payout_delay,
+            &payload.authorized_releasers,
+            &payload.approvers,
+            payload.approval_threshold,
+            &payload.refund_allowlist,
+            &payload.notification_tag,
+            payload.breach_compensation_bps,
+            &payload.step_recipients,
+            &payload.step_bps,
+            payload.confirmations_required
+        ));
 
-    contract_data.serialize(&mut &mut pda.data.borrow_mut()[..])?;
+    let result_code = match validation {
+        Ok(_) => 0,
+        Err(error) => u64::from(error),
+    };
 
-    msg!("Contract created - {}", pda.key);
+    msg!("Create validation result - {}", result_code);
+    set_return_data(&result_code.to_le_bytes());
 
     Ok(())
 }
 
-fn increment_step_handler(
+fn validate_create_contract(
     program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    contract_id: String
-) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-
-    let owner = next_account_info(account_info_iter)?;
-    let worker = next_account_info(account_info_iter)?;
-    let pda = next_account_info(account_info_iter)?;
+    owner: &AccountInfo,
+    worker: &AccountInfo,
+    pda: &AccountInfo,
+    contract_id: &str,
+    total_quantity: u64,
+) -> Result<u8, ProgramError> {
 
-    let (pda_key, _bump_seed) = Pubkey::find_program_address(
-        &[
+    if contract_id.len() > MAX_CONTRACT_ID_LEN {
+        return Err(ContractError::ContractIdTooLong.into());
+    }
+
+    let (_account_len, rent_lamports) =
+        ContractData::get_account_size_and_rent(contract_id.to_string())?;
+
+    let required_lamports = total_quantity
+        .checked_add(rent_lamports)
+        .ok_or(ContractError::AmountOverflow)?;
+
+    if required_lamports > owner.lamports() {
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    validate_worker(program_id, worker)?;
+
+    let (pda_key, bump_seed) = find_contract_address(program_id, owner.key, worker.key, contract_id);
+
+    validate_accounts_on_creation(owner, pda, &pda_key)?;
+
+    Ok(bump_seed)
+}
+
+// The guards on the terms of a new contract, as opposed to its accounts.
+#[allow(clippy::too_many_arguments)]
+fn validate_create_terms(
+    owner: &Pubkey,
+    worker: &Pubkey,
+    payout_delay: i64,
+    authorized_releasers: &[Pubkey],
+    approvers: &[Pubkey],
+    approval_threshold: u8,
+    refund_allowlist: &[Pubkey],
+    notification_tag: &str,
+    breach_compensation_bps: u16,
+    step_recipients: &[Pubkey],
+    step_bps: &[u16],
+    confirmations_required: u8
+) -> ProgramResult {
+
+    if payout_delay < 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if notification_tag.len() > MAX_NOTIFICATION_TAG_LEN {
+        return Err(ContractError::NotificationTagTooLong.into());
+    }
+
+    if breach_compensation_bps > BPS_DENOMINATOR {
+        return Err(ContractError::InvalidCompensationBps.into());
+    }
+
+    if !step_recipients.is_empty() && step_recipients.len() != STEPS as usize {
+        return Err(ContractError::InvalidStepRecipients.into());
+    }
+
+    if !step_bps.is_empty() && (
+        step_bps.len() != STEPS as usize
+            || step_bps.iter().map(|&bps| bps as u64).sum::<u64>() != BPS_DENOMINATOR as u64
+    ) {
+        return Err(ContractError::InvalidStepBps.into());
+    }
+
+    if refund_allowlist.len() > MAX_REFUND_DESTINATIONS {
+        return Err(ContractError::TooManyRefundDestinations.into());
+    }
+
+    validate_signer_sets(
+        owner,
+        worker,
+        authorized_releasers,
+        approvers,
+        approval_threshold,
+        confirmations_required
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_contract<'a>(
+    program_id: &Pubkey,
+    owner: &AccountInfo<'a>,
+    worker: &AccountInfo<'a>,
+    pda: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    registry: Option<&AccountInfo<'a>>,
+    bump_seed: u8,
+    contract_id: String,
+    total_quantity: u64,
+    notify_program: Option<Pubkey>,
+    rounding: RoundingPolicy,
+    payout_delay: i64,
+    authorized_releasers: Vec<Pubkey>,
+    require_proof: bool,
+    approvers: Vec<Pubkey>,
+    approval_threshold: u8,
+    auto_close: bool,
+    refund_allowlist: Vec<Pubkey>,
+    notification_tag: String,
+    max_single_payout: u64,
+    rent_recipient: RentRecipient,
+    dual_increment: bool,
+    breach_compensation_bps: u16,
+    step_recipients: Vec<Pubkey>,
+    registry_program: Option<Pubkey>,
+    step_bps: Vec<u16>,
+    confirmations_required: u8,
+    allow_skip: bool,
+    category: ContractCategory
+) -> ProgramResult {
+
+    let (account_len, rent_lamports) =
+        ContractData::get_account_size_and_rent(contract_id.clone())?;
+
+    invoke_signed(
+        &system_instruction::create_account(
+            owner.key,
+            pda.key,
+            rent_lamports,
+            account_len.try_into().unwrap(),
+            program_id,
+        ),
+        &[owner.clone(), pda.clone(), system_program.clone()],
+        &[&[
             owner.key.as_ref(),
             worker.key.as_ref(),
-            contract_id.as_bytes().as_ref(),
-        ],
-        program_id,
-    );
+            contract_id.as_bytes(),
+            &[bump_seed],
+        ]],
+    )?;
 
-    validate_accounts_on_increment_step(program_id, owner, pda, &pda_key)?;
-    increment_step(worker, pda)
-}
+    invoke(
+        &system_instruction::transfer(owner.key, pda.key, total_quantity),
+        &[owner.clone(), pda.clone(), system_program.clone()],
+    )?;
 
-fn increment_step(worker: &AccountInfo, pda: &AccountInfo) -> ProgramResult {
-    
     let mut contract_data =
-        try_from_slice_unchecked::<ContractData>(&pda.data.borrow())?;
+        read_contract_data(program_id, pda)?;
+
+    contract_data.contract_id = contract_id;
+    contract_data.owner = *owner.key;
+    contract_data.worker = *worker.key;
+    contract_data.total_quantity = total_quantity;
+    contract_data.actual_step = 0;
+    contract_data.notify_program = notify_program;
+    contract_data.status = ContractStatus::Active;
+    contract_data.rounding = rounding;
+    contract_data.frozen = false;
+    contract_data.payout_delay = payout_delay;
+    contract_data.pending_amount = 0;
+    contract_data.pending_since = 0;
+    contract_data.memos = Vec::new();
+    contract_data.authorized_releasers = authorized_releasers;
+    contract_data.last_activity_ts = Clock::get()?.unix_timestamp;
+    contract_data.step_amounts = Vec::new();
+    contract_data.close_requested_at = 0;
+    contract_data.require_proof = require_proof;
+    contract_data.proofs = Vec::new();
+    contract_data.total_disbursed = 0;
+    contract_data.bump = bump_seed;
+    contract_data.approvers = approvers;
+    contract_data.approval_threshold = approval_threshold;
+    contract_data.step_approvals = Vec::new();
+    contract_data.auto_close = auto_close;
+    contract_data.refund_allowlist = refund_allowlist;
+    contract_data.evidence_uris = Vec::new();
+    contract_data.notification_tag = notification_tag;
+    contract_data.max_single_payout = max_single_payout;
+    contract_data.rent_recipient = rent_recipient;
+    contract_data.dual_increment = dual_increment;
+    contract_data.breach_compensation_bps = breach_compensation_bps;
+    contract_data.step_recipients = step_recipients;
+    contract_data.registry_program = registry_program;
+    contract_data.step_bps = step_bps;
+    contract_data.confirmations_required = confirmations_required;
+    contract_data.step_confirmers = 0;
+    contract_data.allow_skip = allow_skip;
+    contract_data.paid_steps = 0;
+    contract_data.pending_step = 0;
+    contract_data.category = category;
+    contract_data.paid_order = Vec::new();
 
-    let transfer_amount = get_transfer_amount(contract_data.total_quantity, contract_data.actual_step)?;
+    recompute_schedule(&mut contract_data)?;
 
-    **worker.lamports.borrow_mut() = worker.lamports()
-        .checked_add(transfer_amount)
-        .ok_or(ProgramError::InsufficientFunds)?;
+    write_contract_data(pda, &contract_data)?;
 
-    **pda.lamports.borrow_mut() = pda.lamports()
-        .checked_sub(transfer_amount)
-        .ok_or(ProgramError::InsufficientFunds)?;
+    contract_msg!(contract_data, "Contract created - {}", pda.key);
+
+    if let Some(registry_program_key) = contract_data.registry_program {
+        let registration = ContractRegistration {
+            contract: *pda.key,
+            owner: contract_data.owner,
+            worker: contract_data.worker,
+            contract_id: contract_data.contract_id,
+            total_quantity: contract_data.total_quantity,
+        };
+
+        register_contract(&registry_program_key, registry, pda, &registration)?;
+    }
+
+    Ok(())
+}
+
+fn increment_step_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String,
+    memo: String,
+    proof: Option<StepProof>,
+    expected_step: Option<u64>,
+    step: Option<u64>
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_named_account(account_info_iter, "Owner")?;
+    let worker = next_named_account(account_info_iter, "Worker")?;
+    let pda = next_named_account(account_info_iter, "Contract")?;
+    let extra_accounts = account_info_iter.as_slice();
+
+    validate_worker(program_id, worker)?;
+
+    if memo.len() > MAX_MEMO_LEN {
+        return Err(ContractError::MemoTooLong.into());
+    }
+
+    if let Some(proof) = &proof {
+        if proof.hash == [0; 32] {
+            return Err(ContractError::InvalidProof.into());
+        }
+
+        if proof.evidence_uri.len() > MAX_EVIDENCE_URI_LEN {
+            return Err(ContractError::EvidenceUriTooLong.into());
+        }
+    }
+
+    let contract_data = validate_contract_account(program_id, owner.key, worker.key, &contract_id, pda)?;
+    validate_steps_remaining(&contract_data)?;
+    warn_if_worker_pays_fees(worker);
+    increment_step(owner, worker, pda, extra_accounts, memo, proof, expected_step, step, contract_data)
+}
+
+// `extra_accounts` holds the contract's notify program, if it has one,
+// followed by any releaser or delegate signing in place of the owner and the
+// step's recipient, if it isn't the worker.
+#[allow(clippy::too_many_arguments)]
+fn increment_step<'a>(
+    owner: &AccountInfo<'a>,
+    worker: &AccountInfo<'a>,
+    pda: &AccountInfo<'a>,
+    extra_accounts: &[AccountInfo<'a>],
+    memo: String,
+    proof: Option<StepProof>,
+    expected_step: Option<u64>,
+    step: Option<u64>,
+    mut contract_data: ContractData
+) -> ProgramResult {
+
+    validate_increment_authority(owner, worker, extra_accounts, &contract_data)?;
+
+    if contract_data.status == ContractStatus::Cancelled {
+        return Err(ContractError::ContractCancelled.into());
+    }
+
+    if contract_data.frozen {
+        return Err(ContractError::StepsFrozen.into());
+    }
+
+    if contract_data.pending_amount > 0 {
+        return Err(ContractError::PayoutPending.into());
+    }
+
+    if contract_data.require_proof {
+        let proof = proof.as_ref().ok_or(ContractError::ProofRequired)?;
+
+        if proof.evidence_uri.is_empty() {
+            return Err(ContractError::EvidenceUriRequired.into());
+        }
+    }
+
+    if contract_data.step_approvals.len() < contract_data.approval_threshold as usize {
+        return Err(ContractError::ApprovalsPending.into());
+    }
+
+    if get_confirmation_count(&contract_data) < contract_data.confirmations_required {
+        return Err(ContractError::ConfirmationsPending.into());
+    }
+
+    let step = match step {
+        Some(step) => validate_specific_step(&contract_data, step)?,
+        None => next_unpaid_step(&contract_data),
+    };
+
+    // Lets a client make sure it is paying the step it thinks it is, and not
+    // another because its view of the contract was stale.
+    if expected_step.is_some_and(|expected_step| expected_step != step) {
+        msg!("Expected step {:?}, contract is paying step {}", expected_step, step);
+        return Err(ContractError::UnexpectedStep.into());
+    }
+
+    let recipient = find_step_recipient(&contract_data, step, worker, extra_accounts)?;
+
+    // Only the final step refunds anything, the rounding remainder and any
+    // surplus, so only it needs a destination off the refund allowlist.
+    let refund_destination = if contract_data.actual_step + 1 >= STEPS {
+        find_refund_destination(&contract_data, owner, extra_accounts)?
+    } else {
+        owner
+    };
 
-    msg!("{} lamports transferred from contract to {}", transfer_amount, worker.key);
+    let IncrementQuote { worker_amount: transfer_amount, owner_remainder, held, .. } =
+        quote_increment(&contract_data, step, recipient)?;
 
+    if contract_data.max_single_payout > 0 && transfer_amount > contract_data.max_single_payout {
+        msg!("Step payout of {} exceeds the cap of {}", transfer_amount, contract_data.max_single_payout);
+        return Err(ContractError::PayoutExceedsCap.into());
+    }
+
+    validate_payout_funds(
+        pda,
+        transfer_amount.checked_add(owner_remainder).ok_or(ProgramError::InvalidAccountData)?
+    )?;
+    validate_escrow_balance(pda, &contract_data)?;
+
+    if held {
+        contract_data.pending_amount = transfer_amount;
+        contract_data.pending_since = Clock::get()?.unix_timestamp;
+        contract_data.pending_step = step;
+
+        if contract_data.payout_delay > 0 {
+            contract_msg!(
+                contract_data,
+                "{} lamports held for {} for {} seconds",
+                transfer_amount,
+                recipient.key,
+                contract_data.payout_delay
+            );
+        } else {
+            contract_msg!(
+                contract_data,
+                "{} lamports held until {} is funded, the payout alone would leave it below rent exemption",
+                transfer_amount,
+                recipient.key
+            );
+        }
+    } else {
+        transfer_lamports(pda, recipient, transfer_amount)?;
+        add_disbursed(&mut contract_data, transfer_amount)?;
+
+        contract_msg!(contract_data, "{} lamports transferred from contract to {}", transfer_amount, recipient.key);
+    }
+
+    if owner_remainder > 0 {
+        transfer_lamports(pda, refund_destination, owner_remainder)?;
+
+        contract_msg!(
+            contract_data,
+            "{} lamports of rounding remainder refunded to {}",
+            owner_remainder,
+            refund_destination.key
+        );
+    }
+
+    let proof = proof.unwrap_or_default();
+
+    contract_data.memos.push(memo);
+    contract_data.proofs.push(proof.hash);
+    contract_data.evidence_uris.push(proof.evidence_uri);
+    contract_data.paid_order.push(step);
+    contract_data.step_approvals.clear();
+    contract_data.step_confirmers = 0;
+    contract_data.delegated_increment = None;
     contract_data.actual_step += 1;
-    contract_data.serialize(&mut &mut pda.data.borrow_mut()[..])?;
+    contract_data.paid_steps |= 1 << step;
+    contract_data.last_activity_ts = Clock::get()?.unix_timestamp;
+    write_contract_data(pda, &contract_data)?;
+
+    if contract_data.actual_step >= STEPS {
+        validate_nothing_stranded(&contract_data, owner_remainder)?;
+        refund_surplus(pda, refund_destination, &contract_data)?;
+    }
+
+    // A final payout that is still held keeps the contract open until it is
+    // released or clawed back.
+    let auto_close = contract_data.auto_close
+        && contract_data.actual_step >= STEPS
+        && contract_data.pending_amount == 0;
+
+    if let Some(notify_program_key) = contract_data.notify_program {
+        let notification = StepNotification {
+            contract_id: contract_data.contract_id.clone(),
+            step,
+            amount: transfer_amount,
+        };
+
+        notify_step(&notify_program_key, extra_accounts.first(), pda, &notification)?;
+    }
+
+    if auto_close {
+        let rent_destination = find_rent_destination(&contract_data, refund_destination, extra_accounts)?;
+        let (escrow_lamports, rent_lamports) = close_contract_account(pda, refund_destination, rent_destination)?;
+
+        contract_msg!(
+            contract_data,
+            "Contract {} closed, {} lamports returned to {}, {} lamports of rent to {}",
+            pda.key,
+            escrow_lamports,
+            refund_destination.key,
+            rent_lamports,
+            rent_destination.key
+        );
+    }
 
     Ok(())
 }
 
-fn validate_accounts_on_creation(
-    owner: &AccountInfo,
-    pda: &AccountInfo,
-    pda_key: &Pubkey
+// A failing CPI aborts the whole transaction, so there is no way to ignore a
+// notification failure here: the notified program must not fail.
+fn notify_step<'a>(
+    notify_program_key: &Pubkey,
+    notify_program: Option<&AccountInfo<'a>>,
+    pda: &AccountInfo<'a>,
+    notification: &StepNotification
 ) -> ProgramResult {
 
-    if pda.key != pda_key {
-        return Err(ProgramError::InvalidAccountData);
+    let notify_program = notify_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+    if notify_program.key != notify_program_key {
+        return Err(ContractError::NotifyProgramMismatch.into());
     }
 
-    if !owner.is_signer {
-        return Err(ProgramError::IllegalOwner);
+    invoke(
+        &solana_program::instruction::Instruction::new_with_borsh(
+            *notify_program.key,
+            notification,
+            vec![AccountMeta::new_readonly(*pda.key, false)],
+        ),
+        &[pda.clone(), notify_program.clone()],
+    )
+}
+
+// Runs after the contract is written, and a failing CPI aborts the whole
+// transaction, so a registry that rejects the contract undoes its creation.
+fn register_contract<'a>(
+    registry_program_key: &Pubkey,
+    registry_program: Option<&AccountInfo<'a>>,
+    pda: &AccountInfo<'a>,
+    registration: &ContractRegistration
+) -> ProgramResult {
+
+    let registry_program = registry_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+    if registry_program.key != registry_program_key {
+        return Err(ContractError::RegistryProgramMismatch.into());
+    }
+
+    invoke(
+        &solana_program::instruction::Instruction::new_with_borsh(
+            *registry_program.key,
+            registration,
+            vec![AccountMeta::new_readonly(*pda.key, false)],
+        ),
+        &[pda.clone(), registry_program.clone()],
+    )
+}
+
+fn refund_unearned_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &contract_id, pda)?;
+
+    let destination = next_refund_destination(account_info_iter, owner, &contract_data)?;
+
+    refund_unearned(destination, pda, contract_data)
+}
+
+fn refund_unearned(destination: &AccountInfo, pda: &AccountInfo, mut contract_data: ContractData) -> ProgramResult {
+
+    if contract_data.status == ContractStatus::Cancelled {
+        return Err(ContractError::ContractCancelled.into());
+    }
+
+    if contract_data.actual_step >= STEPS {
+        return Err(ContractError::AllStepsCompleted.into());
     }
 
+    let refund_amount = get_remaining_amount(&contract_data)?;
+
+    transfer_lamports(pda, destination, refund_amount)?;
+
+    contract_msg!(contract_data, "{} unearned lamports refunded from contract to {}", refund_amount, destination.key);
+
+    contract_data.status = ContractStatus::Cancelled;
+    contract_data.last_activity_ts = Clock::get()?.unix_timestamp;
+    write_contract_data(pda, &contract_data)?;
+
     Ok(())
 }
 
-fn validate_accounts_on_increment_step(
+// Takes an owner, worker and contract triple per contract id, in the same
+// order, and refunds each contract to its owner. Any contract that can't be
+// cancelled fails the instruction, so none of them are.
+fn batch_cancel_handler(
     program_id: &Pubkey,
-    owner: &AccountInfo,
-    pda: &AccountInfo,
-    pda_key: &Pubkey
+    accounts: &[AccountInfo],
+    contract_ids: Vec<String>
 ) -> ProgramResult {
 
-    if pda.key != pda_key {
-        return Err(ProgramError::InvalidAccountData);
+    if contract_ids.is_empty() {
+        return Err(ProgramError::InvalidArgument);
     }
 
-    if pda.owner != program_id {
-        return Err(ProgramError::InvalidAccountData);
+    if contract_ids.len() > MAX_BATCH_SIZE {
+        return Err(ContractError::BatchTooLarge.into());
     }
 
-    if !owner.is_signer {
-        return Err(ProgramError::IllegalOwner);
+    if accounts.len() != contract_ids.len() * 3 {
+        msg!("Expected {} accounts for {} contracts, got {}", contract_ids.len() * 3, contract_ids.len(), accounts.len());
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    for (contract_id, contract_accounts) in contract_ids.iter().zip(accounts.chunks_exact(3)) {
+        let [owner, worker, pda] = contract_accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, contract_id, pda)?;
+
+        // There is no room for a refund destination per contract, so a batch
+        // only refunds contracts whose allowlist lets their owner be one.
+        validate_refund_destination(owner.key, owner.key, &contract_data)?;
+        refund_unearned(owner, pda, contract_data)?;
     }
 
     Ok(())
 }
 
-fn get_transfer_amount(total_quantity: u64, actual_step: u64) -> Result<u64, ProgramError> {
-    match actual_step {
-        0 | 1 => Ok(total_quantity / 3),
-        2 => {
-            let quantity_per_three = total_quantity / 3;
-            Ok(total_quantity - quantity_per_three - quantity_per_three)
-        }
-        _ => {
-            Err(ProgramError::InsufficientFunds)
-        }
+fn get_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String,
+    page: u16
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_contract_account(program_id, owner.key, worker.key, &contract_id, pda)?;
+
+    let view = borsh::to_vec(&ContractView::from(contract_data))?;
+    let (page_count, view_page) = get_view_page(&view, page)?;
+
+    set_return_data(&borsh::to_vec(&ContractViewPage { page, page_count, view: view_page.to_vec() })?);
+
+    Ok(())
+}
+
+fn get_proofs_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_contract_account(program_id, owner.key, worker.key, &contract_id, pda)?;
+
+    set_return_data(&borsh::to_vec(&get_step_records(contract_data))?);
+
+    Ok(())
+}
+
+// Every paid step pushed one of each, so they line up in the order the steps
+// were paid, which is only step order if no step was skipped.
+fn get_step_records(contract_data: ContractData) -> Vec<StepRecord> {
+    let mut records = contract_data.paid_order
+        .into_iter()
+        .zip(contract_data.proofs)
+        .zip(contract_data.memos)
+        .zip(contract_data.evidence_uris)
+        .map(|(((step, proof), memo), evidence_uri)| StepRecord { step, proof, memo, evidence_uri })
+        .collect::<Vec<_>>();
+
+    records.sort_by_key(|record| record.step);
+    records
+}
+
+fn project_remaining_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_contract_account(program_id, owner.key, worker.key, &contract_id, pda)?;
+
+    if contract_data.status == ContractStatus::Cancelled {
+        return Err(ContractError::ContractCancelled.into());
+    }
+
+    let projection = project_remaining(&contract_data, Clock::get()?.unix_timestamp)?;
+
+    set_return_data(&borsh::to_vec(&projection)?);
+
+    Ok(())
+}
+
+// Steps can't be incremented while a payout is held, so with a payout delay
+// every step is paid a full delay after the one before it.
+fn project_remaining(contract_data: &ContractData, now: i64) -> Result<Vec<ProjectedStep>, ProgramError> {
+    let mut ready_at = now;
+
+    if contract_data.pending_amount > 0 {
+        let release_at = contract_data.pending_since
+            .checked_add(contract_data.payout_delay)
+            .ok_or(ProgramError::InvalidAccountData)?;
+
+        ready_at = ready_at.max(release_at);
+    }
+
+    let mut projection = Vec::new();
+
+    for step in get_unpaid_steps(contract_data) {
+        let amount = get_transfer_amount(contract_data, step)?;
+        let earliest_timestamp = ready_at
+            .checked_add(contract_data.payout_delay)
+            .ok_or(ProgramError::InvalidAccountData)?;
+
+        projection.push(ProjectedStep { step, amount, earliest_timestamp });
+        ready_at = earliest_timestamp;
+    }
+
+    Ok(projection)
+}
+
+fn export_snapshot_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String,
+    page: u16
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_contract_account(program_id, owner.key, worker.key, &contract_id, pda)?;
+    let contract = borsh::to_vec(&ContractView::from(contract_data))?;
+    let (page_count, contract_page) = get_view_page(&contract, page)?;
+    let clock = Clock::get()?;
+
+    set_return_data(&borsh::to_vec(&ContractSnapshot {
+        slot: clock.slot,
+        unix_timestamp: clock.unix_timestamp,
+        lamports: pda.lamports(),
+        page,
+        page_count,
+        contract: contract_page.to_vec(),
+    })?);
+
+    Ok(())
+}
+
+// Page `page` of the serialized contract view, along with how many pages there
+// are. An empty view still takes a page, so there is always a page 0.
+fn get_view_page(contract: &[u8], page: u16) -> Result<(u16, &[u8]), ProgramError> {
+    let page_count = u16::try_from(contract.len().div_ceil(VIEW_PAGE_LEN).max(1))
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+
+    if page >= page_count {
+        msg!("Page {} requested, the contract has {}", page, page_count);
+        return Err(ContractError::InvalidViewPage.into());
+    }
+
+    let start = page as usize * VIEW_PAGE_LEN;
+    let end = contract.len().min(start + VIEW_PAGE_LEN);
+
+    Ok((page_count, &contract[start..end]))
+}
+
+fn quote_increment_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_contract_account(program_id, owner.key, worker.key, &contract_id, pda)?;
+
+    if contract_data.status == ContractStatus::Cancelled {
+        return Err(ContractError::ContractCancelled.into());
+    }
+
+    if contract_data.actual_step >= STEPS {
+        return Err(ContractError::AllStepsCompleted.into());
+    }
+
+    let recipient =
+        find_step_recipient(&contract_data, next_unpaid_step(&contract_data), worker, account_info_iter.as_slice())?;
+
+    set_return_data(&borsh::to_vec(&quote_increment(&contract_data, next_unpaid_step(&contract_data), recipient)?)?);
+
+    Ok(())
+}
+
+// Unlike every other handler, a failed check is reported rather than returned
+// as an error, so that operators get the full picture of a broken account.
+fn verify_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_named_account(account_info_iter, "Owner")?;
+    let worker = next_named_account(account_info_iter, "Worker")?;
+    let pda = next_named_account(account_info_iter, "Contract")?;
+
+    let checks = verify(program_id, owner, worker, pda, &contract_id)?;
+
+    msg!("Contract {} verified, checks passed: {:#07b}", pda.key, checks);
+    set_return_data(&[checks]);
+
+    Ok(())
+}
+
+// Only looks at the account's owner and length, so it is as cheap for a
+// contract that was never created as for one that was.
+fn exists_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_named_account(account_info_iter, "Owner")?;
+    let worker = next_named_account(account_info_iter, "Worker")?;
+    let pda = next_named_account(account_info_iter, "Contract")?;
+
+    let (pda_key, _) = find_contract_address(program_id, owner.key, worker.key, &contract_id);
+
+    validate_pda_key(pda, &pda_key)?;
+
+    let exists = pda.owner == program_id && !pda.data_is_empty();
+
+    set_return_data(&[exists as u8]);
+
+    Ok(())
+}
+
+fn verify(
+    program_id: &Pubkey,
+    owner: &AccountInfo,
+    worker: &AccountInfo,
+    pda: &AccountInfo,
+    contract_id: &str
+) -> Result<u8, ProgramError> {
+
+    if pda.owner != program_id {
+        return Ok(0);
+    }
+
+    let mut checks = VERIFY_PROGRAM_OWNED;
+
+    let Ok(contract_data) = read_contract_data(program_id, pda) else {
+        return Ok(checks);
+    };
+
+    checks |= VERIFY_DATA_READABLE;
+
+    if contract_data.actual_step <= STEPS {
+        checks |= VERIFY_STEP_IN_RANGE;
+    }
+
+    if get_expected_lamports(pda, &contract_data).is_ok_and(|expected| pda.lamports() >= expected) {
+        checks |= VERIFY_ESCROW_CONSISTENT;
+    }
+
+    let seeds_match = contract_data.contract_id == contract_id
+        && &contract_data.owner == owner.key
+        && &contract_data.worker == worker.key
+        && create_contract_address(program_id, owner.key, worker.key, contract_id, contract_data.bump)
+            .is_ok_and(|pda_key| &pda_key == pda.key);
+
+    if seeds_match {
+        checks |= VERIFY_SEEDS_MATCH;
+    }
+
+    Ok(checks)
+}
+
+fn set_frozen_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String,
+    frozen: bool
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &contract_id, pda)?;
+    set_frozen(pda, frozen, contract_data)
+}
+
+fn set_frozen(pda: &AccountInfo, frozen: bool, mut contract_data: ContractData) -> ProgramResult {
+
+    if contract_data.status == ContractStatus::Cancelled {
+        return Err(ContractError::ContractCancelled.into());
+    }
+
+    contract_data.frozen = frozen;
+    contract_data.last_activity_ts = Clock::get()?.unix_timestamp;
+    write_contract_data(pda, &contract_data)?;
+
+    if frozen {
+        contract_msg!(contract_data, "New steps frozen - {}", pda.key);
+    } else {
+        contract_msg!(contract_data, "New steps unfrozen - {}", pda.key);
+    }
+
+    Ok(())
+}
+
+fn archive_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+    let archive = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    validate_system_program(system_program)?;
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &contract_id, pda)?;
+
+    let (archive_key, archive_bump_seed) =
+        find_archive_address(program_id, owner.key, worker.key, &contract_id);
+
+    validate_accounts_on_archive(archive, &archive_key)?;
+
+    archive_contract(
+        program_id,
+        owner,
+        worker,
+        pda,
+        archive,
+        system_program,
+        archive_bump_seed,
+        contract_data
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn archive_contract<'a>(
+    program_id: &Pubkey,
+    owner: &AccountInfo<'a>,
+    worker: &AccountInfo<'a>,
+    pda: &AccountInfo<'a>,
+    archive: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    archive_bump_seed: u8,
+    contract_data: ContractData
+) -> ProgramResult {
+
+    if contract_data.status != ContractStatus::Cancelled && contract_data.actual_step < STEPS {
+        return Err(ContractError::ContractNotFinished.into());
+    }
+
+    let account_len = pda.data_len();
+    let rent_lamports = Rent::get()?.minimum_balance(account_len);
+
+    invoke_signed(
+        &system_instruction::create_account(
+            owner.key,
+            archive.key,
+            rent_lamports,
+            account_len.try_into().unwrap(),
+            program_id,
+        ),
+        &[owner.clone(), archive.clone(), system_program.clone()],
+        &[&[
+            ARCHIVE_SEED,
+            owner.key.as_ref(),
+            worker.key.as_ref(),
+            contract_data.contract_id.as_bytes(),
+            &[archive_bump_seed],
+        ]],
+    )?;
+
+    write_contract_data(archive, &contract_data)?;
+
+    contract_msg!(contract_data, "Contract {} archived to {}", pda.key, archive.key);
+
+    Ok(())
+}
+
+fn release_pending_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_named_account(account_info_iter, "Owner")?;
+    let worker = next_named_account(account_info_iter, "Worker")?;
+    let pda = next_named_account(account_info_iter, "Contract")?;
+
+    // Releasing only ever pays the worker what it is owed, so anyone may do it
+    // once the delay has elapsed.
+    let contract_data = validate_contract_account(program_id, owner.key, worker.key, &contract_id, pda)?;
+    release_pending(worker, pda, account_info_iter.as_slice(), contract_data)
+}
+
+fn release_pending<'a>(
+    worker: &AccountInfo<'a>,
+    pda: &AccountInfo,
+    extra_accounts: &[AccountInfo<'a>],
+    mut contract_data: ContractData
+) -> ProgramResult {
+
+    if contract_data.pending_amount == 0 {
+        return Err(ContractError::NoPendingPayout.into());
+    }
+
+    if !is_payout_delay_elapsed(&contract_data)? {
+        return Err(ContractError::PayoutStillLocked.into());
+    }
+
+    let recipient = find_held_step_recipient(&contract_data, worker, extra_accounts)?;
+
+    pay_pending(pda, recipient, &mut contract_data)?;
+    contract_data.last_activity_ts = Clock::get()?.unix_timestamp;
+    write_contract_data(pda, &contract_data)?;
+
+    Ok(())
+}
+
+// Every way a held payout reaches its recipient goes through here, so none of
+// them can pay dust into a closed account or leave it out of the disbursed
+// total.
+fn pay_pending(pda: &AccountInfo, recipient: &AccountInfo, contract_data: &mut ContractData) -> ProgramResult {
+    let pending_amount = contract_data.pending_amount;
+
+    if is_dust_revival(recipient, pending_amount)? {
+        msg!("Worker account {} has to be funded to receive {} lamports", recipient.key, pending_amount);
+        return Err(ContractError::WorkerAccountNotFunded.into());
+    }
+
+    validate_payout_funds(pda, pending_amount)?;
+    validate_escrow_balance(pda, contract_data)?;
+    transfer_lamports(pda, recipient, pending_amount)?;
+    add_disbursed(contract_data, pending_amount)?;
+
+    contract_msg!(contract_data, "{} pending lamports released from contract to {}", pending_amount, recipient.key);
+
+    contract_data.pending_amount = 0;
+    contract_data.pending_since = 0;
+
+    Ok(())
+}
+
+fn clawback_pending_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &contract_id, pda)?;
+
+    let destination = next_refund_destination(account_info_iter, owner, &contract_data)?;
+
+    clawback_pending(destination, pda, contract_data)
+}
+
+fn clawback_pending(destination: &AccountInfo, pda: &AccountInfo, mut contract_data: ContractData) -> ProgramResult {
+
+    if contract_data.pending_amount == 0 {
+        return Err(ContractError::NoPendingPayout.into());
+    }
+
+    if is_payout_delay_elapsed(&contract_data)? {
+        return Err(ContractError::PayoutDelayElapsed.into());
+    }
+
+    let pending_amount = contract_data.pending_amount;
+
+    validate_payout_funds(pda, pending_amount)?;
+    validate_escrow_balance(pda, &contract_data)?;
+    transfer_lamports(pda, destination, pending_amount)?;
+    add_disbursed(&mut contract_data, pending_amount)?;
+
+    contract_msg!(contract_data, "{} pending lamports clawed back from contract to {}", pending_amount, destination.key);
+
+    contract_data.pending_amount = 0;
+    contract_data.pending_since = 0;
+    contract_data.last_activity_ts = Clock::get()?.unix_timestamp;
+    write_contract_data(pda, &contract_data)?;
+
+    Ok(())
+}
+
+fn is_payout_delay_elapsed(contract_data: &ContractData) -> Result<bool, ProgramError> {
+    let release_at = contract_data.pending_since
+        .checked_add(contract_data.payout_delay)
+        .ok_or(ProgramError::InvalidAccountData)?;
+
+    Ok(Clock::get()?.unix_timestamp >= release_at)
+}
+
+fn recover_stuck_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    validate_system_program(system_program)?;
+
+    let (pda_key, bump_seed) = find_contract_address(program_id, owner.key, worker.key, &contract_id);
+
+    validate_accounts_on_recover_stuck(owner, pda, &pda_key)?;
+    recover_stuck(owner, worker, pda, system_program, bump_seed, contract_id)
+}
+
+// A creation that funded the address but never got to allocate it leaves a
+// system-owned account behind with no contract data. Since it is our PDA, we
+// can sign a system transfer for it and hand the lamports back to the owner.
+fn recover_stuck<'a>(
+    owner: &AccountInfo<'a>,
+    worker: &AccountInfo<'a>,
+    pda: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    bump_seed: u8,
+    contract_id: String
+) -> ProgramResult {
+
+    let stuck_lamports = pda.lamports();
+
+    invoke_signed(
+        &system_instruction::transfer(pda.key, owner.key, stuck_lamports),
+        &[pda.clone(), owner.clone(), system_program.clone()],
+        &[&[
+            owner.key.as_ref(),
+            worker.key.as_ref(),
+            contract_id.as_bytes(),
+            &[bump_seed],
+        ]],
+    )?;
+
+    msg!("{} stuck lamports recovered from {} to {}", stuck_lamports, pda.key, owner.key);
+
+    Ok(())
+}
+
+fn update_releasers_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String,
+    releaser: Pubkey,
+    add: bool
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &contract_id, pda)?;
+    let mut authorized_releasers = contract_data.authorized_releasers.clone();
+
+    if add {
+        add_releaser(&mut authorized_releasers, releaser)?;
+    } else {
+        remove_releaser(&mut authorized_releasers, releaser)?;
+    }
+
+    // Releasers can pay steps in place of the owner, so changing them waits
+    // out the same timelock as `ProposeSignerChange`, of which this is one.
+    let approvers = contract_data.approvers.clone();
+
+    propose_signer_change(owner, worker, pda, authorized_releasers, approvers, contract_data)
+}
+
+fn add_releaser(authorized_releasers: &mut Vec<Pubkey>, releaser: Pubkey) -> ProgramResult {
+
+    if authorized_releasers.contains(&releaser) {
+        return Err(ContractError::ReleaserAlreadyAdded.into());
+    }
+
+    if authorized_releasers.len() >= MAX_RELEASERS {
+        return Err(ContractError::TooManyReleasers.into());
+    }
+
+    authorized_releasers.push(releaser);
+
+    Ok(())
+}
+
+fn remove_releaser(authorized_releasers: &mut Vec<Pubkey>, releaser: Pubkey) -> ProgramResult {
+
+    let index = authorized_releasers
+        .iter()
+        .position(|authorized| authorized == &releaser)
+        .ok_or(ContractError::ReleaserNotFound)?;
+
+    authorized_releasers.remove(index);
+
+    Ok(())
+}
+
+fn delegate_increment_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String,
+    delegate: Pubkey,
+    expiry: i64
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &contract_id, pda)?;
+    validate_steps_remaining(&contract_data)?;
+    delegate_increment(pda, delegate, expiry, contract_data)
+}
+
+// Replaces any earlier delegation, so the owner can also use it to move the
+// expiry or hand the increment to someone else.
+fn delegate_increment(pda: &AccountInfo, delegate: Pubkey, expiry: i64, mut contract_data: ContractData) -> ProgramResult {
+
+    let now = Clock::get()?.unix_timestamp;
+
+    if expiry <= now {
+        return Err(ContractError::DelegationExpired.into());
+    }
+
+    contract_data.delegated_increment = Some((delegate, expiry));
+    contract_data.last_activity_ts = now;
+    write_contract_data(pda, &contract_data)?;
+
+    contract_msg!(
+        contract_data,
+        "Increment of step {} of {} delegated to {} until {}",
+        next_unpaid_step(&contract_data),
+        pda.key,
+        delegate,
+        expiry
+    );
+
+    Ok(())
+}
+
+fn approve_step_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_named_account(account_info_iter, "Owner")?;
+    let worker = next_named_account(account_info_iter, "Worker")?;
+    let pda = next_named_account(account_info_iter, "Contract")?;
+    let approver = next_named_account(account_info_iter, "Approver")?;
+
+    let contract_data = validate_contract_account(program_id, owner.key, worker.key, &contract_id, pda)?;
+
+    if !approver.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    approve_step(pda, approver, contract_data)
+}
+
+fn record_confirmation_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_named_account(account_info_iter, "Owner")?;
+    let worker = next_named_account(account_info_iter, "Worker")?;
+    let pda = next_named_account(account_info_iter, "Contract")?;
+    let confirmer = next_named_account(account_info_iter, "Confirmer")?;
+
+    let contract_data = validate_contract_account(program_id, owner.key, worker.key, &contract_id, pda)?;
+
+    if !confirmer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    record_confirmation(pda, confirmer, contract_data)
+}
+
+fn record_confirmation(pda: &AccountInfo, confirmer: &AccountInfo, mut contract_data: ContractData) -> ProgramResult {
+
+    if contract_data.status == ContractStatus::Cancelled {
+        return Err(ContractError::ContractCancelled.into());
+    }
+
+    if contract_data.actual_step >= STEPS {
+        return Err(ContractError::AllStepsCompleted.into());
+    }
+
+    add_confirmation(&mut contract_data, confirmer.key)?;
+    contract_data.last_activity_ts = Clock::get()?.unix_timestamp;
+    write_contract_data(pda, &contract_data)?;
+
+    contract_msg!(
+        contract_data,
+        "Step {} of {} confirmed by {} ({} of {})",
+        next_unpaid_step(&contract_data),
+        pda.key,
+        confirmer.key,
+        get_confirmation_count(&contract_data),
+        contract_data.confirmations_required
+    );
+
+    Ok(())
+}
+
+// Each approver confirms a step at most once, so `confirmations_required`
+// always takes that many distinct approvers.
+fn add_confirmation(contract_data: &mut ContractData, confirmer: &Pubkey) -> ProgramResult {
+    let index = contract_data.approvers
+        .iter()
+        .position(|approver| approver == confirmer)
+        .ok_or(ContractError::ApproverNotFound)?;
+    let confirmer_bit = 1 << index;
+
+    if contract_data.step_confirmers & confirmer_bit != 0 {
+        return Err(ContractError::AlreadyConfirmed.into());
+    }
+
+    contract_data.step_confirmers |= confirmer_bit;
+
+    Ok(())
+}
+
+fn get_confirmation_count(contract_data: &ContractData) -> u8 {
+    contract_data.step_confirmers.count_ones() as u8
+}
+
+// Approvals can come in over any number of transactions, each one is recorded
+// against the current step until that step is paid.
+fn approve_step(pda: &AccountInfo, approver: &AccountInfo, mut contract_data: ContractData) -> ProgramResult {
+
+    if contract_data.status == ContractStatus::Cancelled {
+        return Err(ContractError::ContractCancelled.into());
+    }
+
+    if contract_data.actual_step >= STEPS {
+        return Err(ContractError::AllStepsCompleted.into());
+    }
+
+    if !contract_data.approvers.contains(approver.key) {
+        return Err(ContractError::ApproverNotFound.into());
+    }
+
+    if contract_data.step_approvals.contains(approver.key) {
+        return Err(ContractError::AlreadyApproved.into());
+    }
+
+    contract_data.step_approvals.push(*approver.key);
+    contract_data.last_activity_ts = Clock::get()?.unix_timestamp;
+    write_contract_data(pda, &contract_data)?;
+
+    contract_msg!(
+        contract_data,
+        "Step {} of {} approved by {} ({} of {})",
+        next_unpaid_step(&contract_data),
+        pda.key,
+        approver.key,
+        contract_data.step_approvals.len(),
+        contract_data.approval_threshold
+    );
+
+    Ok(())
+}
+
+fn sweep_abandoned_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    // Sweeping only returns funds to the parties, so anyone may do it once the
+    // contract has been idle long enough.
+    let contract_data = validate_contract_account(program_id, owner.key, worker.key, &contract_id, pda)?;
+    sweep_abandoned(owner, worker, pda, account_info_iter.as_slice(), contract_data)
+}
+
+fn sweep_abandoned<'a, 'b>(
+    owner: &'a AccountInfo<'b>,
+    worker: &'a AccountInfo<'b>,
+    pda: &AccountInfo,
+    extra_accounts: &'a [AccountInfo<'b>],
+    mut contract_data: ContractData
+) -> ProgramResult {
+
+    let abandoned_at = contract_data.last_activity_ts
+        .checked_add(DEAD_CONTRACT_TIMEOUT)
+        .ok_or(ProgramError::InvalidAccountData)?;
+
+    if Clock::get()?.unix_timestamp < abandoned_at {
+        return Err(ContractError::ContractNotAbandoned.into());
+    }
+
+    let destination = find_refund_destination(&contract_data, owner, extra_accounts)?;
+
+    // A held payout has long outlived its delay by now, so it is the worker's.
+    if contract_data.pending_amount > 0 {
+        let recipient = find_held_step_recipient(&contract_data, worker, extra_accounts)?;
+
+        pay_pending(pda, recipient, &mut contract_data)?;
+    }
+
+    let compensation = get_breach_compensation(&contract_data)?;
+
+    if compensation > 0 {
+        transfer_lamports(pda, worker, compensation)?;
+
+        contract_msg!(contract_data, "{} lamports of breach compensation paid from contract to {}", compensation, worker.key);
+    }
+
+    let rent_destination = find_rent_destination(&contract_data, destination, extra_accounts)?;
+    let (swept_lamports, rent_lamports) = close_contract_account(pda, destination, rent_destination)?;
+
+    contract_msg!(
+        contract_data,
+        "Abandoned contract {} swept, {} lamports returned to {}, {} lamports of rent to {}",
+        pda.key,
+        swept_lamports,
+        destination.key,
+        rent_lamports,
+        rent_destination.key
+    );
+
+    Ok(())
+}
+
+fn reduce_scope_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String,
+    new_total: u64
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &contract_id, pda)?;
+
+    if !worker.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let destination = next_refund_destination(account_info_iter, owner, &contract_data)?;
+
+    reduce_scope(destination, pda, new_total, contract_data)
+}
+
+fn reduce_scope(destination: &AccountInfo, pda: &AccountInfo, new_total: u64, mut contract_data: ContractData) -> ProgramResult {
+
+    if contract_data.status == ContractStatus::Cancelled {
+        return Err(ContractError::ContractCancelled.into());
+    }
+
+    if contract_data.actual_step >= STEPS {
+        return Err(ContractError::AllStepsCompleted.into());
+    }
+
+    if new_total >= contract_data.total_quantity {
+        return Err(ContractError::ScopeNotReduced.into());
+    }
+
+    let disbursed_amount = get_disbursed_amount(&contract_data)?;
+
+    if new_total < disbursed_amount {
+        return Err(ContractError::ScopeBelowDisbursed.into());
+    }
+
+    let refund_amount = contract_data.total_quantity - new_total;
+
+    transfer_lamports(pda, destination, refund_amount)?;
+
+    contract_msg!(contract_data, "Scope reduced to {}, {} lamports refunded to {}", new_total, refund_amount, destination.key);
+
+    contract_data.total_quantity = new_total;
+    recompute_schedule(&mut contract_data)?;
+    contract_data.last_activity_ts = Clock::get()?.unix_timestamp;
+    write_contract_data(pda, &contract_data)?;
+
+    Ok(())
+}
+
+fn request_close_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String,
+    request: bool
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &contract_id, pda)?;
+
+    if request {
+        request_close(pda, contract_data)
+    } else {
+        cancel_close_request(pda, contract_data)
+    }
+}
+
+fn request_close(pda: &AccountInfo, mut contract_data: ContractData) -> ProgramResult {
+
+    if contract_data.close_requested_at != 0 {
+        return Err(ContractError::CloseAlreadyRequested.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+
+    contract_data.close_requested_at = now;
+    contract_data.last_activity_ts = now;
+    write_contract_data(pda, &contract_data)?;
+
+    contract_msg!(contract_data, "Close requested - {}, can be confirmed in {} seconds", pda.key, CLOSE_DELAY_SECONDS);
+
+    Ok(())
+}
+
+fn cancel_close_request(pda: &AccountInfo, mut contract_data: ContractData) -> ProgramResult {
+
+    if contract_data.close_requested_at == 0 {
+        return Err(ContractError::CloseNotRequested.into());
+    }
+
+    contract_data.close_requested_at = 0;
+    contract_data.last_activity_ts = Clock::get()?.unix_timestamp;
+    write_contract_data(pda, &contract_data)?;
+
+    contract_msg!(contract_data, "Close request cancelled - {}", pda.key);
+
+    Ok(())
+}
+
+fn propose_signer_change_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String,
+    authorized_releasers: Vec<Pubkey>,
+    approvers: Vec<Pubkey>
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &contract_id, pda)?;
+    propose_signer_change(owner, worker, pda, authorized_releasers, approvers, contract_data)
+}
+
+fn propose_signer_change(
+    owner: &AccountInfo,
+    worker: &AccountInfo,
+    pda: &AccountInfo,
+    authorized_releasers: Vec<Pubkey>,
+    approvers: Vec<Pubkey>,
+    mut contract_data: ContractData
+) -> ProgramResult {
+
+    if contract_data.pending_signer_change.is_some() {
+        return Err(ContractError::SignerChangeAlreadyProposed.into());
+    }
+
+    validate_signer_sets(
+        owner.key,
+        worker.key,
+        &authorized_releasers,
+        &approvers,
+        contract_data.approval_threshold,
+        contract_data.confirmations_required
+    )?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let timelock_expiry = now
+        .checked_add(SIGNER_CHANGE_DELAY_SECONDS)
+        .ok_or(ProgramError::InvalidArgument)?;
+
+    contract_data.pending_signer_change = Some(SignerChange {
+        authorized_releasers,
+        approvers,
+        timelock_expiry,
+    });
+    contract_data.last_activity_ts = now;
+    write_contract_data(pda, &contract_data)?;
+
+    contract_msg!(contract_data, "Signer change proposed - {}, can be applied at {}", pda.key, timelock_expiry);
+
+    Ok(())
+}
+
+fn signer_change_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String,
+    apply: bool
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &contract_id, pda)?;
+
+    if apply {
+        apply_signer_change(pda, contract_data)
+    } else {
+        cancel_signer_change(pda, contract_data)
+    }
+}
+
+fn apply_signer_change(pda: &AccountInfo, mut contract_data: ContractData) -> ProgramResult {
+
+    let signer_change = contract_data.pending_signer_change
+        .take()
+        .ok_or(ContractError::SignerChangeNotProposed)?;
+
+    let now = Clock::get()?.unix_timestamp;
+
+    if now < signer_change.timelock_expiry {
+        return Err(ContractError::SignerChangeStillLocked.into());
+    }
+
+    set_signers(&mut contract_data, signer_change);
+    contract_data.last_activity_ts = now;
+    write_contract_data(pda, &contract_data)?;
+
+    contract_msg!(contract_data, "Signer change applied - {}", pda.key);
+
+    Ok(())
+}
+
+// Approvals and confirmations already given for the current step only count
+// if their approver is still part of the set. Confirmations are kept by
+// approver index, so they move to the approver's index in the new set.
+fn set_signers(contract_data: &mut ContractData, signer_change: SignerChange) {
+    contract_data.step_approvals.retain(|approver| signer_change.approvers.contains(approver));
+    contract_data.step_confirmers = signer_change.approvers
+        .iter()
+        .enumerate()
+        .filter(|(_, approver)| {
+            contract_data.approvers
+                .iter()
+                .position(|old_approver| old_approver == *approver)
+                .is_some_and(|old_index| contract_data.step_confirmers & (1 << old_index) != 0)
+        })
+        .fold(0, |step_confirmers, (index, _)| step_confirmers | (1 << index));
+    contract_data.authorized_releasers = signer_change.authorized_releasers;
+    contract_data.approvers = signer_change.approvers;
+}
+
+fn cancel_signer_change(pda: &AccountInfo, mut contract_data: ContractData) -> ProgramResult {
+
+    if contract_data.pending_signer_change.take().is_none() {
+        return Err(ContractError::SignerChangeNotProposed.into());
+    }
+
+    contract_data.last_activity_ts = Clock::get()?.unix_timestamp;
+    write_contract_data(pda, &contract_data)?;
+
+    contract_msg!(contract_data, "Signer change cancelled - {}", pda.key);
+
+    Ok(())
+}
+
+fn confirm_close_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &contract_id, pda)?;
+
+    let destination = next_refund_destination(account_info_iter, owner, &contract_data)?;
+
+    confirm_close(destination, pda, account_info_iter.as_slice(), contract_data)
+}
+
+fn confirm_close<'a, 'b>(
+    destination: &'a AccountInfo<'b>,
+    pda: &AccountInfo,
+    extra_accounts: &'a [AccountInfo<'b>],
+    contract_data: ContractData
+) -> ProgramResult {
+
+    if contract_data.close_requested_at == 0 {
+        return Err(ContractError::CloseNotRequested.into());
+    }
+
+    let closable_at = contract_data.close_requested_at
+        .checked_add(CLOSE_DELAY_SECONDS)
+        .ok_or(ProgramError::InvalidAccountData)?;
+
+    if Clock::get()?.unix_timestamp < closable_at {
+        return Err(ContractError::CloseStillLocked.into());
+    }
+
+    // A held payout has to be released or clawed back first, so closing never
+    // decides who it belongs to.
+    if contract_data.pending_amount > 0 {
+        return Err(ContractError::PayoutPending.into());
+    }
+
+    let rent_destination = find_rent_destination(&contract_data, destination, extra_accounts)?;
+    let (escrow_lamports, rent_lamports) = close_contract_account(pda, destination, rent_destination)?;
+
+    contract_msg!(
+        contract_data,
+        "Contract {} closed, {} lamports returned to {}, {} lamports of rent to {}",
+        pda.key,
+        escrow_lamports,
+        destination.key,
+        rent_lamports,
+        rent_destination.key
+    );
+
+    Ok(())
+}
+
+fn close_contract_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_account_info(account_info_iter)?;
+    let worker = next_account_info(account_info_iter)?;
+    let pda = next_account_info(account_info_iter)?;
+
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &contract_id, pda)?;
+
+    let destination = next_refund_destination(account_info_iter, owner, &contract_data)?;
+
+    close_contract(destination, pda, account_info_iter.as_slice(), contract_data)
+}
+
+// Once a contract is finished there is nothing left for the worker to lose,
+// so unlike `ConfirmClose` there is no cooling-off period.
+fn close_contract<'a, 'b>(
+    destination: &'a AccountInfo<'b>,
+    pda: &AccountInfo,
+    extra_accounts: &'a [AccountInfo<'b>],
+    contract_data: ContractData
+) -> ProgramResult {
+
+    if contract_data.status != ContractStatus::Cancelled && contract_data.actual_step < STEPS {
+        return Err(ContractError::ContractNotFinished.into());
+    }
+
+    if contract_data.pending_amount > 0 {
+        return Err(ContractError::PayoutPending.into());
+    }
+
+    let rent_destination = find_rent_destination(&contract_data, destination, extra_accounts)?;
+    let (escrow_lamports, rent_lamports) = close_contract_account(pda, destination, rent_destination)?;
+
+    contract_msg!(
+        contract_data,
+        "Contract {} closed, {} lamports returned to {}, {} lamports of rent to {}",
+        pda.key,
+        escrow_lamports,
+        destination.key,
+        rent_lamports,
+        rent_destination.key
+    );
+
+    Ok(())
+}
+
+// Moves every lamport out of the contract account and hands it back to the
+// system program, so the address can be reused. The contract account is ours,
+// so its lamports are moved directly rather than through a system transfer.
+// Returns the lamports sent to `destination` and to `rent_destination`.
+fn close_contract_account(
+    pda: &AccountInfo,
+    destination: &AccountInfo,
+    rent_destination: &AccountInfo
+) -> Result<(u64, u64), ProgramError> {
+
+    let rent_lamports = Rent::get()?.minimum_balance(pda.data_len()).min(pda.lamports());
+
+    transfer_lamports(pda, rent_destination, rent_lamports)?;
+
+    let escrow_lamports = pda.lamports();
+
+    transfer_lamports(pda, destination, escrow_lamports)?;
+
+    pda.realloc(0, false)?;
+    pda.assign(&system_program::ID);
+
+    Ok((escrow_lamports, rent_lamports))
+}
+
+fn rollover_handler(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    old_contract_id: String,
+    new_contract_id: String
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let owner = next_named_account(account_info_iter, "Owner")?;
+    let worker = next_named_account(account_info_iter, "Worker")?;
+    let old_pda = next_named_account(account_info_iter, "Old contract")?;
+    let new_pda = next_named_account(account_info_iter, "New contract")?;
+    let system_program = next_named_account(account_info_iter, "System program")?;
+
+    validate_system_program(system_program)?;
+
+    if old_contract_id == new_contract_id {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let contract_data = validate_accounts_on_increment_step(program_id, owner, worker, &old_contract_id, old_pda)?;
+    rollover(
+        program_id,
+        owner,
+        worker,
+        old_pda,
+        new_pda,
+        system_program,
+        account_info_iter.next(),
+        new_contract_id,
+        contract_data
+    )
+}
+
+// The old contract is closed into the owner, who then funds the new one with
+// exactly what was left of the old escrow, all within the same instruction.
+// The new contract keeps the old one's terms with a fresh schedule.
+#[allow(clippy::too_many_arguments)]
+fn rollover<'a>(
+    program_id: &Pubkey,
+    owner: &AccountInfo<'a>,
+    worker: &AccountInfo<'a>,
+    old_pda: &AccountInfo<'a>,
+    new_pda: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    registry: Option<&AccountInfo<'a>>,
+    new_contract_id: String,
+    contract_data: ContractData
+) -> ProgramResult {
+
+    if contract_data.status == ContractStatus::Cancelled {
+        return Err(ContractError::ContractCancelled.into());
+    }
+
+    if contract_data.actual_step >= STEPS {
+        return Err(ContractError::AllStepsCompleted.into());
+    }
+
+    if contract_data.pending_amount > 0 {
+        return Err(ContractError::PayoutPending.into());
+    }
+
+    let remaining_amount = get_remaining_amount(&contract_data)?;
+
+    // The old contract's rent goes towards the new one's, so it stays with the
+    // owner whatever the rent recipient.
+    close_contract_account(old_pda, owner, owner)?;
+
+    contract_msg!(contract_data, "Contract {} closed for rollover, {} lamports carried over", old_pda.key, remaining_amount);
+
+    let bump_seed = validate_create_contract(
+        program_id,
+        owner,
+        worker,
+        new_pda,
+        &new_contract_id,
+        remaining_amount
+    )?;
+
+    create_contract(
+        program_id,
+        owner,
+        worker,
+        new_pda,
+        system_program,
+        registry,
+        bump_seed,
+        new_contract_id,
+        remaining_amount,
+        contract_data.notify_program,
+        contract_data.rounding,
+        contract_data.payout_delay,
+        contract_data.authorized_releasers,
+        contract_data.require_proof,
+        contract_data.approvers,
+        contract_data.approval_threshold,
+        contract_data.auto_close,
+        contract_data.refund_allowlist,
+        contract_data.notification_tag,
+        contract_data.max_single_payout,
+        contract_data.rent_recipient,
+        contract_data.dual_increment,
+        contract_data.breach_compensation_bps,
+        contract_data.step_recipients,
+        contract_data.registry_program,
+        contract_data.step_bps,
+        contract_data.confirmations_required,
+        contract_data.allow_skip,
+        contract_data.category
+    )
+}
+
+fn get_claimable_handler(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let worker = next_account_info(account_info_iter)?;
+    let contracts = account_info_iter.as_slice();
+
+    if contracts.len() > MAX_CLAIMABLE_CONTRACTS {
+        return Err(ContractError::TooManyContracts.into());
+    }
+
+    let claimable = contracts
+        .iter()
+        .map(|pda| get_claimable(program_id, worker, pda))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    set_return_data(&borsh::to_vec(&claimable)?);
+
+    Ok(())
+}
+
+fn get_states_handler(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+
+    if accounts.len() > MAX_STATE_CONTRACTS {
+        return Err(ContractError::TooManyContracts.into());
+    }
+
+    let states = accounts
+        .iter()
+        .map(|pda| get_state(program_id, pda))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    set_return_data(&borsh::to_vec(&states)?);
+
+    Ok(())
+}
+
+fn get_state(program_id: &Pubkey, pda: &AccountInfo) -> Result<ContractState, ProgramError> {
+
+    let contract_data =
+        read_contract_data(program_id, pda)?;
+    let pda_key = create_contract_address(
+        program_id,
+        &contract_data.owner,
+        &contract_data.worker,
+        &contract_data.contract_id,
+        contract_data.bump
+    )?;
+
+    // Any account the program owns reads as a contract if its bytes happen to
+    // decode, an archive among them, so only the account at its own address
+    // counts.
+    validate_pda_key(pda, &pda_key)?;
+
+    Ok(ContractState {
+        contract: *pda.key,
+        actual_step: contract_data.actual_step,
+        status: contract_data.status,
+        frozen: contract_data.frozen,
+        total_quantity: contract_data.total_quantity,
+        total_disbursed: contract_data.total_disbursed,
+        pending_amount: contract_data.pending_amount,
+        category: contract_data.category,
+    })
+}
+
+fn get_claimable(
+    program_id: &Pubkey,
+    worker: &AccountInfo,
+    pda: &AccountInfo
+) -> Result<ClaimablePayout, ProgramError> {
+
+    let contract_data =
+        read_contract_data(program_id, pda)?;
+
+    if &contract_data.worker != worker.key {
+        return Err(ContractError::WorkerMismatch.into());
+    }
+
+    let pda_key = create_contract_address(
+        program_id,
+        &contract_data.owner,
+        &contract_data.worker,
+        &contract_data.contract_id,
+        contract_data.bump
+    )?;
+
+    validate_pda_key(pda, &pda_key)?;
+
+    let amount = if contract_data.pending_amount > 0 && is_payout_delay_elapsed(&contract_data)? {
+        contract_data.pending_amount
+    } else {
+        0
+    };
+
+    Ok(ClaimablePayout { contract: *pda.key, amount })
+}
+
+// Refunds go to the owner unless the caller passes another destination after
+// the contract account, which then has to be on the refund allowlist.
+fn next_refund_destination<'a, 'b>(
+    account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    owner: &'a AccountInfo<'b>,
+    contract_data: &ContractData
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    let destination = account_info_iter.next().unwrap_or(owner);
+
+    validate_refund_destination(destination.key, owner.key, contract_data)?;
+
+    Ok(destination)
+}
+
+// For the paths whose trailing accounts are looked up by key rather than
+// read in order: the owner if the allowlist is empty, otherwise the first
+// account passed that is on it.
+fn find_refund_destination<'a, 'b>(
+    contract_data: &ContractData,
+    owner: &'a AccountInfo<'b>,
+    accounts: &'a [AccountInfo<'b>]
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    if contract_data.refund_allowlist.is_empty() {
+        return Ok(owner);
+    }
+
+    std::iter::once(owner)
+        .chain(accounts)
+        .find(|account| contract_data.refund_allowlist.contains(account.key))
+        .ok_or_else(|| {
+            msg!("No refund destination on the allowlist provided");
+            ContractError::RefundDestinationNotAllowed.into()
+        })
+}
+
+fn validate_refund_destination(
+    destination: &Pubkey,
+    owner: &Pubkey,
+    contract_data: &ContractData
+) -> ProgramResult {
+    let allowed = if contract_data.refund_allowlist.is_empty() {
+        destination == owner
+    } else {
+        contract_data.refund_allowlist.contains(destination)
+    };
+
+    if !allowed {
+        msg!("Refund destination {} is not allowed", destination);
+        return Err(ContractError::RefundDestinationNotAllowed.into());
+    }
+
+    Ok(())
+}
+
+fn tag_prefix(notification_tag: &str) -> String {
+    if notification_tag.is_empty() {
+        String::new()
+    } else {
+        format!("[{}] ", notification_tag)
+    }
+}
+
+// The account paid for `step`: the worker, unless the contract has step
+// recipients, in which case the step's recipient has to be among `accounts`.
+fn find_step_recipient<'a, 'b>(
+    contract_data: &ContractData,
+    step: u64,
+    worker: &'a AccountInfo<'b>,
+    accounts: &'a [AccountInfo<'b>]
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    let Some(recipient) = contract_data.step_recipients.get(step as usize) else {
+        return Ok(worker);
+    };
+
+    accounts
+        .iter()
+        .find(|account| account.key == recipient)
+        .ok_or_else(|| {
+            msg!("Recipient {} of step {} not provided", recipient, step);
+            ProgramError::NotEnoughAccountKeys
+        })
+}
+
+// A held payout belongs to `pending_step`, which was already counted as paid
+// when the payout was held.
+fn find_held_step_recipient<'a, 'b>(
+    contract_data: &ContractData,
+    worker: &'a AccountInfo<'b>,
+    accounts: &'a [AccountInfo<'b>]
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    find_step_recipient(contract_data, contract_data.pending_step, worker, accounts)
+}
+
+// With a treasury rent recipient the treasury can be anywhere in `accounts`,
+// but it has to come after the refund destination if one is passed.
+fn find_rent_destination<'a, 'b>(
+    contract_data: &ContractData,
+    destination: &'a AccountInfo<'b>,
+    accounts: &'a [AccountInfo<'b>]
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    match contract_data.rent_recipient {
+        RentRecipient::Owner => Ok(destination),
+        RentRecipient::Treasury(treasury) => accounts
+            .iter()
+            .find(|account| account.key == &treasury)
+            .ok_or_else(|| {
+                msg!("Treasury account {} not provided", treasury);
+                ProgramError::NotEnoughAccountKeys
+            }),
+    }
+}
+
+// Same as `next_account_info`, but says which account was missing, so that
+// a client leaving out e.g. the worker gets more than a bare
+// `NotEnoughAccountKeys`.
+fn next_named_account<'a, 'b>(
+    account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    name: &str
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    next_account_info(account_info_iter).inspect_err(|_| {
+        msg!("{} account not provided", name);
+    })
+}
+
+// The fee payer is not visible to the program, and the owner leads our
+// account list whether or not it pays, so the first signer says nothing. Only
+// a signer can pay the fees though, so a worker signing the increment, on its
+// own or as the owner, may be paying them.
+fn warn_if_worker_pays_fees(worker: &AccountInfo) {
+    if worker.is_signer {
+        msg!(
+            "Warning: worker {} signed and may be the fee payer, its net balance change may differ from the payout",
+            worker.key
+        );
+    }
+}
+
+fn ping() -> ProgramResult {
+    msg!("Program version - {}", VERSION);
+    set_return_data(VERSION.as_bytes());
+
+    Ok(())
+}
+
+fn get_limits() -> ProgramResult {
+    let limits = ProgramLimits {
+        max_contract_id_len: MAX_CONTRACT_ID_LEN as u64,
+        steps: STEPS,
+        max_memo_len: MAX_MEMO_LEN as u64,
+        max_notification_tag_len: MAX_NOTIFICATION_TAG_LEN as u64,
+        max_evidence_uri_len: MAX_EVIDENCE_URI_LEN as u64,
+        max_releasers: MAX_RELEASERS as u64,
+        max_approvers: MAX_APPROVERS as u64,
+        max_refund_destinations: MAX_REFUND_DESTINATIONS as u64,
+        max_claimable_contracts: MAX_CLAIMABLE_CONTRACTS as u64,
+        max_state_contracts: MAX_STATE_CONTRACTS as u64,
+        max_batch_size: MAX_BATCH_SIZE as u64,
+        dead_contract_timeout: DEAD_CONTRACT_TIMEOUT,
+        close_delay_seconds: CLOSE_DELAY_SECONDS,
+        signer_change_delay_seconds: SIGNER_CHANGE_DELAY_SECONDS,
+    };
+
+    set_return_data(&borsh::to_vec(&limits)?);
+
+    Ok(())
+}
+
+fn validate_accounts_on_creation(
+    owner: &AccountInfo,
+    pda: &AccountInfo,
+    pda_key: &Pubkey
+) -> ProgramResult {
+
+    validate_pda_key(pda, pda_key)?;
+
+    if !pda.is_writable {
+        return Err(ContractError::PdaNotWritable.into());
+    }
+
+    if !owner.is_signer {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    // A contract keeps its seeds taken until it is closed, whether or not it
+    // was cancelled: closing empties the account and hands it back to the
+    // system program, and only then can the id be created afresh.
+    if pda.lamports() > 0 || !pda.data_is_empty() {
+        return Err(ContractError::ContractAlreadyExists.into());
+    }
+
+    Ok(())
+}
+
+fn validate_accounts_on_archive(archive: &AccountInfo, archive_key: &Pubkey) -> ProgramResult {
+
+    validate_pda_key(archive, archive_key)?;
+
+    if archive.lamports() > 0 {
+        return Err(ContractError::ArchiveAlreadyExists.into());
+    }
+
+    Ok(())
+}
+
+fn validate_accounts_on_recover_stuck(
+    owner: &AccountInfo,
+    pda: &AccountInfo,
+    pda_key: &Pubkey
+) -> ProgramResult {
+
+    validate_pda_key(pda, pda_key)?;
+
+    if !owner.is_signer {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    if pda.owner != &system_program::ID || !pda.data_is_empty() || pda.lamports() == 0 {
+        return Err(ContractError::ContractNotStuck.into());
+    }
+
+    Ok(())
+}
+
+fn validate_pda_key(pda: &AccountInfo, pda_key: &Pubkey) -> ProgramResult {
+
+    if pda.key != pda_key {
+        msg!("PDA mismatch: expected {}, provided {}", pda_key, pda.key);
+        return Err(ContractError::PdaMismatch.into());
+    }
+
+    Ok(())
+}
+
+// The runtime would also refuse to run a system instruction against another
+// program, but checking first keeps every handler that invokes the system
+// program failing the same way, before anything else is read.
+fn validate_system_program(system_program: &AccountInfo) -> ProgramResult {
+
+    if system_program.key != &system_program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    Ok(())
+}
+
+// Shared by creation and `ProposeSignerChange`, so a rotated set is held to
+// the same limits as the one the contract was created with.
+fn validate_signer_sets(
+    owner: &Pubkey,
+    worker: &Pubkey,
+    authorized_releasers: &[Pubkey],
+    approvers: &[Pubkey],
+    approval_threshold: u8,
+    confirmations_required: u8
+) -> ProgramResult {
+
+    if authorized_releasers.len() > MAX_RELEASERS {
+        return Err(ContractError::TooManyReleasers.into());
+    }
+
+    if approvers.len() > MAX_APPROVERS {
+        return Err(ContractError::TooManyApprovers.into());
+    }
+
+    if approval_threshold as usize > approvers.len() {
+        return Err(ContractError::InvalidApprovalThreshold.into());
+    }
+
+    // Approvals are meant to come from outside the contract, the owner or the
+    // worker approving its own step would defeat the point.
+    if approvers.iter().any(|approver| approver == owner || approver == worker) {
+        return Err(ContractError::ApproverIsParty.into());
+    }
+
+    // Each approver confirms a step at most once, so asking for more
+    // confirmations than there are approvers could never be met.
+    if confirmations_required as usize > approvers.len() {
+        return Err(ContractError::InvalidConfirmationsRequired.into());
+    }
+
+    Ok(())
+}
+
+fn validate_worker(program_id: &Pubkey, worker: &AccountInfo) -> ProgramResult {
+
+    if worker.key == program_id {
+        return Err(ContractError::WorkerIsProgram.into());
+    }
+
+    Ok(())
+}
+
+fn validate_accounts_on_increment_step(
+    program_id: &Pubkey,
+    owner: &AccountInfo,
+    worker: &AccountInfo,
+    contract_id: &str,
+    pda: &AccountInfo
+) -> Result<ContractData, ProgramError> {
+
+    let contract_data = validate_contract_account(program_id, owner.key, worker.key, contract_id, pda)?;
+
+    if !owner.is_signer {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    Ok(contract_data)
+}
+
+// Done while validating, so that every way of incrementing rejects a finished
+// contract the same way rather than failing to find the next payout.
+fn validate_steps_remaining(contract_data: &ContractData) -> ProgramResult {
+    if contract_data.actual_step as usize >= contract_data.step_amounts.len() {
+        return Err(ContractError::AllStepsCompleted.into());
+    }
+
+    Ok(())
+}
+
+fn validate_specific_step(contract_data: &ContractData, step: u64) -> Result<u64, ProgramError> {
+    if !contract_data.allow_skip {
+        return Err(ContractError::SkipNotAllowed.into());
+    }
+
+    if step >= STEPS {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if is_step_paid(contract_data, step) {
+        msg!("Step {} of {} is already paid", step, contract_data.contract_id);
+        return Err(ContractError::StepAlreadyPaid.into());
+    }
+
+    Ok(step)
+}
+
+fn validate_increment_authority(
+    owner: &AccountInfo,
+    worker: &AccountInfo,
+    extra_accounts: &[AccountInfo],
+    contract_data: &ContractData
+) -> ProgramResult {
+
+    if contract_data.dual_increment {
+        if !owner.is_signer {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        if !worker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        return Ok(());
+    }
+
+    if owner.is_signer {
+        return Ok(());
+    }
+
+    let releaser_signed = extra_accounts
+        .iter()
+        .any(|account| account.is_signer && contract_data.authorized_releasers.contains(account.key));
+
+    if releaser_signed {
+        return Ok(());
+    }
+
+    if let Some((delegate, expiry)) = contract_data.delegated_increment {
+        let delegate_signed = extra_accounts
+            .iter()
+            .any(|account| account.is_signer && account.key == &delegate);
+
+        if delegate_signed {
+            if Clock::get()?.unix_timestamp > expiry {
+                msg!("Delegation to {} expired at {}", delegate, expiry);
+                return Err(ContractError::DelegationExpired.into());
+            }
+
+            return Ok(());
+        }
+    }
+
+    Err(ProgramError::IllegalOwner)
+}
+
+// Checks `pda` against the address derived from the bump stored at creation,
+// which is both cheaper than searching for it again and guarantees the account
+// is the one that was created for these seeds. Reading the bump means reading
+// the contract, so it is handed back for the caller to use rather than read
+// again.
+fn validate_contract_account(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    worker: &Pubkey,
+    contract_id: &str,
+    pda: &AccountInfo
+) -> Result<ContractData, ProgramError> {
+
+    let contract_data =
+        read_contract_data(program_id, pda)?;
+    let pda_key = create_contract_address(program_id, owner, worker, contract_id, contract_data.bump)?;
+
+    validate_pda_key(pda, &pda_key)?;
+
+    // The address only proves which seeds were used, the stored owner is the
+    // one that counts.
+    if &contract_data.owner != owner {
+        msg!("Contract owner is {}, provided {}", contract_data.owner, owner);
+        return Err(ContractError::WrongOwner.into());
+    }
+
+    Ok(contract_data)
+}
+
+// Every read of a contract goes through here, so an account that is not one
+// fails the same way whichever instruction it was passed to.
+fn read_contract_data(program_id: &Pubkey, pda: &AccountInfo) -> Result<ContractData, ProgramError> {
+
+    if pda.owner != program_id {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let min_len = ContractData::get_account_size("");
+
+    if pda.data_len() < min_len {
+        msg!("Contract {} holds {} bytes, at least {} expected", pda.key, pda.data_len(), min_len);
+        return Err(ContractError::CorruptData.into());
+    }
+
+    try_from_slice_unchecked::<ContractData>(&pda.data.borrow()).map_err(|err| {
+        msg!("Contract {} could not be read: {}", pda.key, err);
+        ContractError::CorruptData.into()
+    })
+}
+
+// Serializes first and copies only a buffer that fits, so a contract that
+// outgrew its account is a clean error rather than a failed write halfway
+// through the account data.
+fn write_contract_data(account: &AccountInfo, contract_data: &ContractData) -> ProgramResult {
+    let data = borsh::to_vec(contract_data)?;
+    let mut account_data = account.data.borrow_mut();
+
+    if data.len() > account_data.len() {
+        msg!("Contract needs {} bytes, account {} holds {}", data.len(), account.key, account_data.len());
+        return Err(ContractError::AccountTooSmall.into());
+    }
+
+    account_data[..data.len()].copy_from_slice(&data);
+
+    Ok(())
+}
+
+fn find_contract_address(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    worker: &Pubkey,
+    contract_id: &str
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            owner.as_ref(),
+            worker.as_ref(),
+            contract_id.as_bytes(),
+        ],
+        program_id,
+    )
+}
+
+fn create_contract_address(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    worker: &Pubkey,
+    contract_id: &str,
+    bump_seed: u8
+) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(
+        &[
+            owner.as_ref(),
+            worker.as_ref(),
+            contract_id.as_bytes(),
+            &[bump_seed],
+        ],
+        program_id,
+    )
+    .map_err(|_| ContractError::PdaMismatch.into())
+}
+
+fn find_archive_address(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    worker: &Pubkey,
+    contract_id: &str
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            ARCHIVE_SEED,
+            owner.as_ref(),
+            worker.as_ref(),
+            contract_id.as_bytes(),
+        ],
+        program_id,
+    )
+}
+
+// What paying `step` moves out of the contract. `IncrementStep` pays exactly
+// this, so a `QuoteIncrement` can't disagree with it.
+fn quote_increment(
+    contract_data: &ContractData,
+    step: u64,
+    worker: &AccountInfo
+) -> Result<IncrementQuote, ProgramError> {
+    let worker_amount = get_transfer_amount(contract_data, step)?;
+
+    Ok(IncrementQuote {
+        step,
+        worker_amount,
+        owner_remainder: get_owner_remainder(contract_data)?,
+        held: contract_data.payout_delay > 0 || is_dust_revival(worker, worker_amount)?,
+    })
+}
+
+// A worker account that was closed since the contract was created would be
+// revived by the payout, and left below rent exemption if the payout is too
+// small. Such a payout stays held until the worker funds the account.
+fn is_dust_revival(worker: &AccountInfo, amount: u64) -> Result<bool, ProgramError> {
+    if worker.lamports() > 0 || !worker.data_is_empty() {
+        return Ok(false);
+    }
+
+    Ok(amount < Rent::get()?.minimum_balance(0))
+}
+
+fn get_transfer_amount(contract_data: &ContractData, step: u64) -> Result<u64, ProgramError> {
+    contract_data.step_amounts
+        .get(step as usize)
+        .copied()
+        .ok_or(ProgramError::InsufficientFunds)
+}
+
+// Lamports refunded to the owner, on top of the worker payout, when paying
+// the next step. Whatever part of `total_quantity` the schedule leaves
+// unassigned goes back to the owner with the last step to be paid.
+fn get_owner_remainder(contract_data: &ContractData) -> Result<u64, ProgramError> {
+    if contract_data.actual_step as usize + 1 != contract_data.step_amounts.len() {
+        return Ok(0);
+    }
+
+    contract_data.total_quantity
+        .checked_sub(sum_amounts(&contract_data.step_amounts)?)
+        .ok_or(ProgramError::InvalidAccountData)
+}
+
+// Amount committed to the steps already paid: what has left the escrow plus
+// any payout still held.
+fn get_disbursed_amount(contract_data: &ContractData) -> Result<u64, ProgramError> {
+    contract_data.total_disbursed
+        .checked_add(contract_data.pending_amount)
+        .ok_or(ProgramError::InvalidAccountData)
+}
+
+fn add_disbursed(contract_data: &mut ContractData, amount: u64) -> ProgramResult {
+    contract_data.total_disbursed = contract_data.total_disbursed
+        .checked_add(amount)
+        .ok_or(ProgramError::InvalidAccountData)?;
+
+    Ok(())
+}
+
+// Precondition of every payout: paying `amount` must leave the contract rent
+// exempt. Failing it means the escrow was drained or never fully funded.
+fn validate_payout_funds(pda: &AccountInfo, amount: u64) -> ProgramResult {
+    let required_lamports = Rent::get()?
+        .minimum_balance(pda.data_len())
+        .checked_add(amount)
+        .ok_or(ProgramError::InvalidAccountData)?;
+
+    if pda.lamports() < required_lamports {
+        msg!(
+            "Contract holds {} lamports, {} short of the {} needed to pay {}",
+            pda.lamports(),
+            required_lamports - pda.lamports(),
+            required_lamports,
+            amount
+        );
+        return Err(ContractError::InsufficientEscrow.into());
+    }
+
+    Ok(())
+}
+
+// Consistency of the bookkeeping with the account: the contract has to hold at
+// least what `total_disbursed` and its status say hasn't left it yet.
+fn validate_escrow_balance(pda: &AccountInfo, contract_data: &ContractData) -> ProgramResult {
+    let expected_lamports = get_expected_lamports(pda, contract_data)?;
+
+    if pda.lamports() < expected_lamports {
+        msg!("Contract holds {} lamports, expected at least {}", pda.lamports(), expected_lamports);
+        return Err(ContractError::EscrowShortfall.into());
+    }
+
+    Ok(())
+}
+
+// Only checks the escrow the program is responsible for: once every step is
+// paid, all of `total_quantity` has to be disbursed, held or refunded as the
+// rounding remainder. The account balance can't be used for this, as anyone
+// can send lamports to the contract.
+fn validate_nothing_stranded(contract_data: &ContractData, owner_remainder: u64) -> ProgramResult {
+    let routed_lamports = get_disbursed_amount(contract_data)?
+        .checked_add(owner_remainder)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    let stranded_lamports = contract_data.total_quantity.saturating_sub(routed_lamports);
+
+    if stranded_lamports > MAX_STRANDED_LAMPORTS {
+        msg!("{} lamports of {} would be left stranded", stranded_lamports, contract_data.contract_id);
+        return Err(ContractError::StrandedLamports.into());
+    }
+
+    Ok(())
+}
+
+// Lamports sent to the contract from outside have no party in the schedule,
+// so the final step hands them to the owner rather than leaving them behind.
+fn refund_surplus(pda: &AccountInfo, destination: &AccountInfo, contract_data: &ContractData) -> ProgramResult {
+    let surplus_lamports = pda.lamports().saturating_sub(get_expected_lamports(pda, contract_data)?);
+
+    if surplus_lamports > 0 {
+        transfer_lamports(pda, destination, surplus_lamports)?;
+
+        contract_msg!(contract_data, "{} surplus lamports refunded to {}", surplus_lamports, destination.key);
+    }
+
+    Ok(())
+}
+
+// Rent plus whatever of the escrow has not left the contract yet.
+fn get_expected_lamports(pda: &AccountInfo, contract_data: &ContractData) -> Result<u64, ProgramError> {
+    Rent::get()?
+        .minimum_balance(pda.data_len())
+        .checked_add(get_undisbursed_escrow(contract_data)?)
+        .ok_or(ProgramError::InvalidAccountData)
+}
+
+// Once the contract is cancelled or finished only a held payout is left, the
+// rest having been refunded to the owner without counting as disbursed.
+fn get_undisbursed_escrow(contract_data: &ContractData) -> Result<u64, ProgramError> {
+    if contract_data.status == ContractStatus::Cancelled || contract_data.actual_step >= STEPS {
+        return Ok(contract_data.pending_amount);
+    }
+
+    contract_data.total_quantity
+        .checked_sub(contract_data.total_disbursed)
+        .ok_or(ProgramError::InvalidAccountData)
+}
+
+// Only a contract left with steps to pay was abandoned by its owner, a
+// cancelled or finished one has no unearned escrow left to share.
+fn get_breach_compensation(contract_data: &ContractData) -> Result<u64, ProgramError> {
+    if contract_data.status == ContractStatus::Cancelled || contract_data.actual_step >= STEPS {
+        return Ok(0);
+    }
+
+    let compensation = get_remaining_amount(contract_data)? as u128
+        * contract_data.breach_compensation_bps as u128
+        / BPS_DENOMINATOR as u128;
+
+    u64::try_from(compensation).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+// Everything still held for the unpaid steps, whether it ends up with the
+// worker or is refunded to the owner.
+fn get_remaining_amount(contract_data: &ContractData) -> Result<u64, ProgramError> {
+    contract_data.total_quantity
+        .checked_sub(get_disbursed_amount(contract_data)?)
+        .ok_or(ProgramError::InvalidAccountData)
+}
+
+// Rebuilds the payouts of the unpaid steps from whatever of `total_quantity`
+// is not committed to the paid ones yet, leaving the paid steps as they were.
+// Every change to the terms a schedule depends on goes through here.
+fn recompute_schedule(contract_data: &mut ContractData) -> ProgramResult {
+    let unpaid_steps = get_unpaid_steps(contract_data);
+    let undisbursed_amount = contract_data.total_quantity
+        .checked_sub(get_disbursed_amount(contract_data)?)
+        .ok_or(ContractError::ScopeBelowDisbursed)?;
+    let remaining_schedule = if contract_data.step_bps.is_empty() {
+        build_schedule(undisbursed_amount, unpaid_steps.len() as u64, contract_data.rounding)?
+    } else {
+        let weights = unpaid_steps
+            .iter()
+            .map(|&step| contract_data.step_bps[step as usize])
+            .collect::<Vec<_>>();
+
+        build_weighted_schedule(undisbursed_amount, &weights)
+    };
+
+    contract_data.step_amounts.resize(STEPS as usize, 0);
+
+    for (step, amount) in unpaid_steps.into_iter().zip(remaining_schedule) {
+        contract_data.step_amounts[step as usize] = amount;
+    }
+
+    Ok(())
+}
+
+fn is_step_paid(contract_data: &ContractData, step: u64) -> bool {
+    contract_data.paid_steps & (1 << step) != 0
+}
+
+// The step `IncrementStep` pays. Paying in order this is `actual_step`, but
+// once a step was skipped it is the lowest one still unpaid.
+fn next_unpaid_step(contract_data: &ContractData) -> u64 {
+    contract_data.paid_steps.trailing_ones() as u64
+}
+
+fn get_unpaid_steps(contract_data: &ContractData) -> Vec<u64> {
+    (0..STEPS).filter(|&step| !is_step_paid(contract_data, step)).collect()
+}
+
+// Splits `amount` into `steps` payouts following the rounding policy. Under
+// `FavorOwner` the payouts add up to less than `amount`, the difference being
+// refunded with the final step.
+fn build_schedule(
+    amount: u64,
+    steps: u64,
+    rounding: RoundingPolicy
+) -> Result<Vec<u64>, ProgramError> {
+    let (per_step, remainder) = divide_with_remainder(amount, steps)?;
+
+    let schedule = (0..steps)
+        .map(|step| match rounding {
+            RoundingPolicy::FavorWorker if step == steps - 1 => per_step + remainder,
+            RoundingPolicy::FavorWorker | RoundingPolicy::FavorOwner => per_step,
+            RoundingPolicy::Banker => {
+                get_banker_share(amount, step + 1, steps) - get_banker_share(amount, step, steps)
+            }
+        })
+        .collect();
+
+    Ok(schedule)
+}
+
+// Splits `amount` in proportion to `weights`, each payout floored and the
+// final one absorbing what the flooring left, so the payouts always add up to
+// `amount`. The rounding policy only applies to even splits.
+fn build_weighted_schedule(amount: u64, weights: &[u16]) -> Vec<u64> {
+    let total_weight = weights.iter().map(|&weight| weight as u128).sum::<u128>();
+
+    let mut schedule = weights
+        .iter()
+        .map(|&weight| match total_weight {
+            0 => 0,
+            _ => (amount as u128 * weight as u128 / total_weight) as u64,
+        })
+        .collect::<Vec<_>>();
+
+    let assigned = schedule.iter().sum::<u64>();
+
+    if let Some(last) = schedule.last_mut() {
+        *last += amount - assigned;
+    }
+
+    schedule
+}
+
+// Cumulative amount owed after `step` of `steps` steps, rounded half to even.
+fn get_banker_share(amount: u64, step: u64, steps: u64) -> u64 {
+    let numerator = amount as u128 * step as u128;
+    let denominator = steps as u128;
+
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+
+    let share = match (2 * remainder).cmp(&denominator) {
+        std::cmp::Ordering::Less => quotient,
+        std::cmp::Ordering::Equal => quotient + (quotient & 1),
+        std::cmp::Ordering::Greater => quotient + 1,
+    };
+
+    share as u64
+}
+
+fn sum_amounts(amounts: &[u64]) -> Result<u64, ProgramError> {
+    amounts
+        .iter()
+        .try_fold(0u64, |sum, amount| sum.checked_add(*amount))
+        .ok_or(ProgramError::InvalidAccountData)
+}
+
+fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> ProgramResult {
+
+    **to.lamports.borrow_mut() = to.lamports()
+        .checked_add(amount)
+        .ok_or(ProgramError::InsufficientFunds)?;
+
+    **from.lamports.borrow_mut() = from.lamports()
+        .checked_sub(amount)
+        .ok_or(ProgramError::InsufficientFunds)?;
+
+    Ok(())
+}
+
+// Returns `(per_part, remainder)`, leaving it to the caller to decide which
+// part absorbs the remainder.
+fn divide_with_remainder(total: u64, parts: u64) -> Result<(u64, u64), ProgramError> {
+    let per_part = total
+        .checked_div(parts)
+        .ok_or(ProgramError::InvalidArgument)?;
+    let remainder = total
+        .checked_rem(parts)
+        .ok_or(ProgramError::InvalidArgument)?;
+
+    Ok((per_part, remainder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::{
+        entrypoint::SUCCESS,
+        program::get_return_data,
+        program_stubs::{set_syscall_stubs, SyscallStubs},
+    };
+    use std::{cell::RefCell, sync::Once};
+
+    // The unix timestamp `Clock::get` returns once `stub_syscalls` ran.
+    const TEST_NOW: i64 = 1_700_000_000;
+
+    thread_local! {
+        // Tests run on threads of their own, so each sees only the return
+        // data it set.
+        static RETURN_DATA: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+    }
+
+    struct TestSyscalls;
+
+    impl SyscallStubs for TestSyscalls {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock { unix_timestamp: TEST_NOW, ..Clock::default() };
+            unsafe { *(var_addr as *mut Clock) = clock };
+            SUCCESS
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            SUCCESS
+        }
+
+        fn sol_set_return_data(&self, data: &[u8]) {
+            RETURN_DATA.with(|return_data| *return_data.borrow_mut() = Some(data.to_vec()));
+        }
+
+        fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+            RETURN_DATA.with(|return_data| return_data.borrow().clone()).map(|data| (Pubkey::default(), data))
+        }
+    }
+
+    // Off-chain the sysvars and return data are unavailable, so handlers
+    // using them need them stubbed first.
+    fn stub_syscalls() {
+        static STUB: Once = Once::new();
+        STUB.call_once(|| {
+            set_syscall_stubs(Box::new(TestSyscalls));
+        });
+    }
+
+    fn returned_data() -> Vec<u8> {
+        get_return_data().map(|(_, data)| data).unwrap_or_default()
+    }
+
+    // A freshly allocated contract account, as `create_contract` finds it.
+    fn new_contract_data(total_quantity: u64) -> ContractData {
+        let zeroed = vec![0; ContractData::get_account_size("")];
+        let mut contract_data = ContractData::deserialize(&mut zeroed.as_slice()).unwrap();
+
+        contract_data.total_quantity = total_quantity;
+        contract_data
+    }
+
+    // A contract created by `owner` for `worker`, along with the address it
+    // lives at.
+    fn stored_contract(program_id: &Pubkey, owner: &Pubkey, worker: &Pubkey) -> (Pubkey, ContractData) {
+        let mut contract_data = new_contract_data(300);
+        contract_data.contract_id = "contract".to_string();
+        contract_data.owner = *owner;
+        contract_data.worker = *worker;
+
+        let (pda_key, bump_seed) = find_contract_address(program_id, owner, worker, &contract_data.contract_id);
+        contract_data.bump = bump_seed;
+
+        (pda_key, contract_data)
+    }
+
+    // The bytes of an account holding `contract_data`.
+    fn account_bytes(contract_data: &ContractData) -> Vec<u8> {
+        let mut data = borsh::to_vec(contract_data).unwrap();
+        data.resize(ContractData::get_account_size(&contract_data.contract_id), 0);
+        data
+    }
+
+    // Owns what an `AccountInfo` borrows, so a test can hand out several.
+    struct TestAccount {
+        key: Pubkey,
+        owner: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+        is_signer: bool,
+        is_writable: bool,
+    }
+
+    impl TestAccount {
+        fn new(key: Pubkey, owner: Pubkey, lamports: u64, data: Vec<u8>) -> Self {
+            Self { key, owner, lamports, data, is_signer: false, is_writable: true }
+        }
+
+        // An empty system account, such as a wallet with nothing in it.
+        fn wallet(key: Pubkey) -> Self {
+            Self::new(key, system_program::ID, 0, Vec::new())
+        }
+
+        fn signer(mut self) -> Self {
+            self.is_signer = true;
+            self
+        }
+
+        fn read_only(mut self) -> Self {
+            self.is_writable = false;
+            self
+        }
+
+        fn info(&mut self) -> AccountInfo<'_> {
+            AccountInfo::new(
+                &self.key,
+                self.is_signer,
+                self.is_writable,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                false,
+                0
+            )
+        }
+    }
+
+    fn pay_step(contract_data: &mut ContractData, step: u64) {
+        add_disbursed(contract_data, contract_data.step_amounts[step as usize]).unwrap();
+        contract_data.paid_steps |= 1 << step;
+        contract_data.actual_step += 1;
+    }
+
+    #[test]
+    fn divide_with_remainder_conserves_total() {
+        for (total, parts) in [(0, 1), (10, 3), (100, 3), (7, 7), (2, 5), (u64::MAX, 3)] {
+            let (per_part, remainder) = divide_with_remainder(total, parts).unwrap();
+
+            assert!(remainder < parts);
+            assert_eq!(per_part as u128 * parts as u128 + remainder as u128, total as u128);
+        }
+    }
+
+    #[test]
+    fn divide_with_remainder_rejects_zero_parts() {
+        assert_eq!(divide_with_remainder(10, 0), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn build_schedule_applies_rounding_policy() {
+        assert_eq!(build_schedule(100, 3, RoundingPolicy::FavorWorker).unwrap(), vec![33, 33, 34]);
+        assert_eq!(build_schedule(100, 3, RoundingPolicy::FavorOwner).unwrap(), vec![33, 33, 33]);
+        assert_eq!(build_schedule(100, 3, RoundingPolicy::Banker).unwrap(), vec![33, 34, 33]);
+    }
+
+    #[test]
+    fn build_schedule_conserves_amount() {
+        for amount in [0, 1, 2, 100, 101, 1_000_003, u64::MAX] {
+            for steps in 1..=5 {
+                for rounding in [RoundingPolicy::FavorWorker, RoundingPolicy::FavorOwner, RoundingPolicy::Banker] {
+                    let schedule = build_schedule(amount, steps, rounding).unwrap();
+                    let total = schedule.iter().map(|&amount| amount as u128).sum::<u128>();
+
+                    assert_eq!(schedule.len() as u64, steps);
+
+                    match rounding {
+                        RoundingPolicy::FavorOwner => assert!(amount as u128 - total < steps as u128),
+                        _ => assert_eq!(total, amount as u128),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn archive_address_differs_from_suffixed_contract_address() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let worker = Pubkey::new_unique();
+
+        assert_ne!(
+            find_archive_address(&program_id, &owner, &worker, "X").0,
+            find_contract_address(&program_id, &owner, &worker, "Xarchive").0
+        );
+    }
+
+    #[test]
+    fn recompute_schedule_after_scope_reduction_keeps_paid_steps() {
+        let mut contract_data = new_contract_data(300);
+        recompute_schedule(&mut contract_data).unwrap();
+        pay_step(&mut contract_data, 0);
+
+        contract_data.total_quantity = 240;
+        recompute_schedule(&mut contract_data).unwrap();
+
+        assert_eq!(contract_data.step_amounts, vec![100, 70, 70]);
+    }
+
+    #[test]
+    fn recompute_schedule_rejects_scope_below_disbursed() {
+        let mut contract_data = new_contract_data(300);
+        recompute_schedule(&mut contract_data).unwrap();
+        pay_step(&mut contract_data, 0);
+
+        contract_data.total_quantity = 99;
+
+        assert_eq!(
+            recompute_schedule(&mut contract_data),
+            Err(ContractError::ScopeBelowDisbursed.into())
+        );
+    }
+
+    #[test]
+    fn undisbursed_escrow_follows_contract_status() {
+        let mut contract_data = new_contract_data(300);
+        recompute_schedule(&mut contract_data).unwrap();
+        assert_eq!(get_undisbursed_escrow(&contract_data), Ok(300));
+
+        pay_step(&mut contract_data, 0);
+        assert_eq!(get_undisbursed_escrow(&contract_data), Ok(200));
+
+        // Held payouts are still in the contract, and not disbursed yet.
+        contract_data.pending_amount = 100;
+        contract_data.actual_step += 1;
+        assert_eq!(get_undisbursed_escrow(&contract_data), Ok(200));
+
+        // Refunding the unearned escrow leaves only the held payout.
+        contract_data.status = ContractStatus::Cancelled;
+        assert_eq!(get_undisbursed_escrow(&contract_data), Ok(100));
+    }
+
+    #[test]
+    fn undisbursed_escrow_of_finished_contract_excludes_owner_remainder() {
+        let mut contract_data = new_contract_data(100);
+        contract_data.rounding = RoundingPolicy::FavorOwner;
+        recompute_schedule(&mut contract_data).unwrap();
+
+        for step in 0..STEPS {
+            pay_step(&mut contract_data, step);
+        }
+
+        assert_eq!(contract_data.total_disbursed, 99);
+        assert_eq!(get_undisbursed_escrow(&contract_data), Ok(0));
+    }
+
+    #[test]
+    fn contract_address_only_matches_stored_bump() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let worker = Pubkey::new_unique();
+        let (pda_key, bump_seed) = find_contract_address(&program_id, &owner, &worker, "X");
+
+        assert_eq!(create_contract_address(&program_id, &owner, &worker, "X", bump_seed), Ok(pda_key));
+
+        for forged_bump in (0..=u8::MAX).filter(|&bump| bump != bump_seed) {
+            assert_ne!(create_contract_address(&program_id, &owner, &worker, "X", forged_bump), Ok(pda_key));
+        }
+    }
+
+    fn create_payload(contract_id: &str, total_quantity: u64) -> Vec<u8> {
+        let mut payload = borsh::to_vec(&(
+            contract_id.to_string(),
+            total_quantity,
+            None::<Pubkey>,
+            RoundingPolicy::FavorWorker,
+            0i64,
+            Vec::<Pubkey>::new(),
+            false,
+            Vec::<Pubkey>::new(),
+            0u8,
+            false,
+        )).unwrap();
+
+        payload.extend(borsh::to_vec(&(
+            Vec::<Pubkey>::new(),
+            String::new(),
+            0u64,
+            RentRecipient::Owner,
+            false,
+            0u16,
+            Vec::<Pubkey>::new(),
+            None::<Pubkey>,
+            Vec::<u16>::new(),
+            0u8,
+            false,
+            ContractCategory::Bounty,
+        )).unwrap());
+
+        payload
+    }
+
+    fn instruction_data(variant: u8, payload: &[u8]) -> Vec<u8> {
+        [&[variant], payload].concat()
+    }
+
+    #[test]
+    fn unpack_round_trips_create_payload() {
+        let data = instruction_data(0, &create_payload("job", 300));
+
+        assert!(matches!(
+            Instruction::unpack(&data),
+            Ok(Instruction::CreateContract { contract_id, total_quantity: 300, category: ContractCategory::Bounty, .. })
+                if contract_id == "job"
+        ));
+    }
+
+    #[test]
+    fn unpack_round_trips_increment_payload() {
+        let data = instruction_data(1, &borsh::to_vec(&("job".to_string(), Some(2u64))).unwrap());
+
+        assert!(matches!(
+            Instruction::unpack(&data),
+            Ok(Instruction::IncrementStep { contract_id, expected_step: Some(2) }) if contract_id == "job"
+        ));
+    }
+
+    #[test]
+    fn unpack_round_trips_payloadless_variant() {
+        assert!(matches!(Instruction::unpack(&[2]), Ok(Instruction::Ping)));
+    }
+
+    #[test]
+    fn unpack_rejects_payload_of_another_variant() {
+        let data = instruction_data(1, &create_payload("job", 300));
+
+        assert!(matches!(Instruction::unpack(&data), Err(ProgramError::InvalidInstructionData)));
+        assert!(matches!(Instruction::unpack(&[2, 0]), Err(ProgramError::InvalidInstructionData)));
+    }
+
+    fn complete_schedule(total_quantity: u64, rounding: RoundingPolicy) -> (ContractData, u64) {
+        let mut contract_data = new_contract_data(total_quantity);
+        contract_data.rounding = rounding;
+        recompute_schedule(&mut contract_data).unwrap();
+
+        let owner_remainder = (0..STEPS)
+            .map(|step| {
+                let owner_remainder = get_owner_remainder(&contract_data).unwrap();
+                pay_step(&mut contract_data, step);
+                owner_remainder
+            })
+            .sum();
+
+        (contract_data, owner_remainder)
+    }
+
+    #[test]
+    fn final_step_strands_nothing() {
+        for total_quantity in [0, 1, 2, 99, 100, 101, 1_000_000_007] {
+            for rounding in [RoundingPolicy::FavorWorker, RoundingPolicy::FavorOwner, RoundingPolicy::Banker] {
+                let (contract_data, owner_remainder) = complete_schedule(total_quantity, rounding);
+
+                assert_eq!(validate_nothing_stranded(&contract_data, owner_remainder), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn final_step_counts_a_held_payout_as_routed() {
+        let (mut contract_data, owner_remainder) = complete_schedule(100, RoundingPolicy::FavorWorker);
+        contract_data.total_disbursed -= 34;
+        contract_data.pending_amount = 34;
+
+        assert_eq!(validate_nothing_stranded(&contract_data, owner_remainder), Ok(()));
+    }
+
+    #[test]
+    fn final_step_rejects_unrouted_escrow() {
+        let (mut contract_data, owner_remainder) = complete_schedule(100, RoundingPolicy::FavorWorker);
+        contract_data.total_quantity += 1;
+
+        assert_eq!(
+            validate_nothing_stranded(&contract_data, owner_remainder),
+            Err(ContractError::StrandedLamports.into())
+        );
+    }
+
+    #[test]
+    fn unpack_rejects_empty_input() {
+        assert!(matches!(Instruction::unpack(&[]), Err(ProgramError::InvalidInstructionData)));
+    }
+
+    #[test]
+    fn unpack_rejects_tag_without_payload() {
+        for variant in [0, 1, 3, 39] {
+            assert!(matches!(Instruction::unpack(&[variant]), Err(ProgramError::InvalidInstructionData)));
+        }
+    }
+
+    #[test]
+    fn unpack_rejects_unknown_tag() {
+        assert!(matches!(Instruction::unpack(&[u8::MAX]), Err(ProgramError::InvalidInstructionData)));
+    }
+
+    fn unpaid_amount(contract_data: &ContractData) -> u64 {
+        get_unpaid_steps(contract_data)
+            .into_iter()
+            .map(|step| contract_data.step_amounts[step as usize])
+            .sum()
+    }
+
+    #[test]
+    fn recompute_schedule_conserves_undisbursed_balance() {
+        for step_bps in [vec![], vec![2_000, 3_000, 5_000]] {
+            for new_total in [150, 301, 1_000_003] {
+                let mut contract_data = new_contract_data(300);
+                contract_data.step_bps = step_bps.clone();
+                recompute_schedule(&mut contract_data).unwrap();
+                pay_step(&mut contract_data, 0);
+
+                // Stands for a top-up when above 300 and a scope reduction below.
+                contract_data.total_quantity = new_total;
+                recompute_schedule(&mut contract_data).unwrap();
+
+                assert_eq!(unpaid_amount(&contract_data), new_total - contract_data.total_disbursed);
+            }
+        }
+    }
+
+    #[test]
+    fn refund_to_owner_follows_allowlist() {
+        let owner = Pubkey::new_unique();
+        let mut contract_data = new_contract_data(300);
+        assert_eq!(validate_refund_destination(&owner, &owner, &contract_data), Ok(()));
+
+        contract_data.refund_allowlist = vec![Pubkey::new_unique()];
+        assert_eq!(
+            validate_refund_destination(&owner, &owner, &contract_data),
+            Err(ContractError::RefundDestinationNotAllowed.into())
+        );
+
+        contract_data.refund_allowlist.push(owner);
+        assert_eq!(validate_refund_destination(&owner, &owner, &contract_data), Ok(()));
+    }
+
+    #[test]
+    fn releaser_set_edits() {
+        let releaser = Pubkey::new_unique();
+        let mut authorized_releasers = Vec::new();
+
+        assert_eq!(add_releaser(&mut authorized_releasers, releaser), Ok(()));
+        assert_eq!(
+            add_releaser(&mut authorized_releasers, releaser),
+            Err(ContractError::ReleaserAlreadyAdded.into())
+        );
+
+        while authorized_releasers.len() < MAX_RELEASERS {
+            add_releaser(&mut authorized_releasers, Pubkey::new_unique()).unwrap();
+        }
+
+        assert_eq!(
+            add_releaser(&mut authorized_releasers, Pubkey::new_unique()),
+            Err(ContractError::TooManyReleasers.into())
+        );
+
+        assert_eq!(remove_releaser(&mut authorized_releasers, releaser), Ok(()));
+        assert_eq!(
+            remove_releaser(&mut authorized_releasers, releaser),
+            Err(ContractError::ReleaserNotFound.into())
+        );
+    }
+
+    #[test]
+    fn breach_compensation_is_share_of_remaining_escrow() {
+        let mut contract_data = new_contract_data(300);
+        contract_data.breach_compensation_bps = 2_500;
+        recompute_schedule(&mut contract_data).unwrap();
+        assert_eq!(get_breach_compensation(&contract_data), Ok(75));
+
+        pay_step(&mut contract_data, 0);
+        assert_eq!(get_breach_compensation(&contract_data), Ok(50));
+
+        contract_data.breach_compensation_bps = BPS_DENOMINATOR;
+        assert_eq!(get_breach_compensation(&contract_data), Ok(200));
+    }
+
+    #[test]
+    fn breach_compensation_only_applies_to_open_contracts() {
+        let mut contract_data = new_contract_data(300);
+        contract_data.breach_compensation_bps = 2_500;
+        recompute_schedule(&mut contract_data).unwrap();
+
+        contract_data.status = ContractStatus::Cancelled;
+        assert_eq!(get_breach_compensation(&contract_data), Ok(0));
+
+        contract_data.status = ContractStatus::Active;
+        for step in 0..STEPS {
+            pay_step(&mut contract_data, step);
+        }
+        assert_eq!(get_breach_compensation(&contract_data), Ok(0));
+    }
+
+    #[test]
+    fn weighted_schedule_follows_bps() {
+        let step_bps = [2_500, 2_500, 5_000];
+
+        assert_eq!(build_weighted_schedule(1_000, &step_bps), vec![250, 250, 500]);
+        assert_eq!(build_weighted_schedule(1_001, &step_bps), vec![250, 250, 501]);
+        assert_eq!(build_weighted_schedule(0, &step_bps), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn weighted_schedule_conserves_amount() {
+        for amount in [1, 3, 999, 1_000_003, u64::MAX] {
+            for step_bps in [[2_500, 2_500, 5_000], [1, 1, 9_998], [3_333, 3_333, 3_334]] {
+                let schedule = build_weighted_schedule(amount, &step_bps);
+
+                assert_eq!(schedule.iter().map(|&amount| amount as u128).sum::<u128>(), amount as u128);
+            }
+        }
+    }
+
+    #[test]
+    fn recompute_schedule_uses_step_bps() {
+        let mut contract_data = new_contract_data(1_000);
+        contract_data.step_bps = vec![2_500, 2_500, 5_000];
+        recompute_schedule(&mut contract_data).unwrap();
+
+        assert_eq!(contract_data.step_amounts, vec![250, 250, 500]);
+    }
+
+    #[test]
+    fn signer_sets_need_approvers_for_confirmations() {
+        let owner = Pubkey::new_unique();
+        let worker = Pubkey::new_unique();
+        let approvers = [Pubkey::new_unique()];
+
+        assert_eq!(validate_signer_sets(&owner, &worker, &[], &approvers, 0, 1), Ok(()));
+        assert_eq!(validate_signer_sets(&owner, &worker, &[], &[], 0, 0), Ok(()));
+        assert_eq!(
+            validate_signer_sets(&owner, &worker, &[], &[], 0, 1),
+            Err(ContractError::InvalidConfirmationsRequired.into())
+        );
+        assert_eq!(
+            validate_signer_sets(&owner, &worker, &[], &approvers, 0, 2),
+            Err(ContractError::InvalidConfirmationsRequired.into())
+        );
+    }
+
+    fn unique_keys(count: usize) -> Vec<Pubkey> {
+        (0..count).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    // A contract with every list and string at its limit.
+    fn full_contract_data() -> ContractData {
+        let mut contract_data = new_contract_data(u64::MAX);
+
+        contract_data.contract_id = "c".repeat(MAX_CONTRACT_ID_LEN);
+        contract_data.notify_program = Some(Pubkey::new_unique());
+        contract_data.memos = vec!["m".repeat(MAX_MEMO_LEN); STEPS as usize];
+        contract_data.authorized_releasers = unique_keys(MAX_RELEASERS);
+        contract_data.step_amounts = vec![u64::MAX; STEPS as usize];
+        contract_data.proofs = vec![[1; 32]; STEPS as usize];
+        contract_data.approvers = unique_keys(MAX_APPROVERS);
+        contract_data.step_approvals = unique_keys(MAX_APPROVERS);
+        contract_data.refund_allowlist = unique_keys(MAX_REFUND_DESTINATIONS);
+        contract_data.evidence_uris = vec!["e".repeat(MAX_EVIDENCE_URI_LEN); STEPS as usize];
+        contract_data.notification_tag = "t".repeat(MAX_NOTIFICATION_TAG_LEN);
+        contract_data.rent_recipient = RentRecipient::Treasury(Pubkey::new_unique());
+        contract_data.delegated_increment = Some((Pubkey::new_unique(), i64::MAX));
+        contract_data.pending_signer_change = Some(SignerChange {
+            authorized_releasers: unique_keys(MAX_RELEASERS),
+            approvers: unique_keys(MAX_APPROVERS),
+            timelock_expiry: i64::MAX,
+        });
+        contract_data.step_recipients = unique_keys(STEPS as usize);
+        contract_data.registry_program = Some(Pubkey::new_unique());
+        contract_data.step_bps = vec![u16::MAX; STEPS as usize];
+        contract_data.paid_order = vec![u64::MAX; STEPS as usize];
+        contract_data
+    }
+
+    #[test]
+    fn full_contract_fits_its_account() {
+        let contract_data = full_contract_data();
+
+        assert!(borsh::to_vec(&contract_data).unwrap().len() <= ContractData::get_account_size(&contract_data.contract_id));
+    }
+
+    #[test]
+    fn snapshot_pages_round_trip_a_full_contract() {
+        let contract_data = full_contract_data();
+        let contract_id = contract_data.contract_id.clone();
+        let contract = borsh::to_vec(&ContractView::from(contract_data)).unwrap();
+        let (page_count, _) = get_view_page(&contract, 0).unwrap();
+        let mut exported = Vec::new();
+
+        // A full contract is bigger than the return data, hence the pages.
+        assert!(contract.len() > MAX_RETURN_DATA);
+
+        for page in 0..page_count {
+            let (_, contract_page) = get_view_page(&contract, page).unwrap();
+            let snapshot = borsh::to_vec(&ContractSnapshot {
+                slot: 1,
+                unix_timestamp: 2,
+                lamports: 3,
+                page,
+                page_count,
+                contract: contract_page.to_vec(),
+            }).unwrap();
+
+            assert!(snapshot.len() <= MAX_RETURN_DATA);
+
+            exported.extend(ContractSnapshot::try_from_slice(&snapshot).unwrap().contract);
+        }
+
+        assert_eq!(exported, contract);
+
+        let view = ContractView::try_from_slice(&exported).unwrap();
+        assert_eq!(view.version, CONTRACT_VIEW_VERSION);
+        assert_eq!(view.contract_id, contract_id);
+    }
+
+    #[test]
+    fn get_pages_round_trip_a_full_contract() {
+        let mut contract_data = full_contract_data();
+        contract_data.total_disbursed = 200;
+        let contract = borsh::to_vec(&ContractView::from(contract_data)).unwrap();
+        let (page_count, _) = get_view_page(&contract, 0).unwrap();
+        let mut view = Vec::new();
+
+        for page in 0..page_count {
+            let (_, view_page) = get_view_page(&contract, page).unwrap();
+            let result = borsh::to_vec(&ContractViewPage { page, page_count, view: view_page.to_vec() }).unwrap();
+
+            assert!(result.len() <= MAX_RETURN_DATA);
+
+            view.extend(ContractViewPage::try_from_slice(&result).unwrap().view);
+        }
+
+        let view = ContractView::try_from_slice(&view).unwrap();
+        assert_eq!(view.version, CONTRACT_VIEW_VERSION);
+        assert_eq!(view.total_disbursed, 200);
+        assert_eq!(view.memos.len(), STEPS as usize);
+    }
+
+    #[test]
+    fn unpack_reads_get_page() {
+        let data = instruction_data(5, &borsh::to_vec(&("job".to_string(), 2u16)).unwrap());
+
+        assert!(matches!(
+            Instruction::unpack(&data),
+            Ok(Instruction::Get { ref contract_id, page: 2 }) if contract_id == "job"
+        ));
+    }
+
+    #[test]
+    fn snapshot_rejects_page_past_the_end() {
+        let contract = vec![0; VIEW_PAGE_LEN + 1];
+
+        assert_eq!(get_view_page(&contract, 1).unwrap().1, &[0][..]);
+        assert_eq!(get_view_page(&contract, 2), Err(ContractError::InvalidViewPage.into()));
+        assert_eq!(get_view_page(&[], 0), Ok((1, &[][..])));
+    }
+
+    #[test]
+    fn next_unpaid_step_follows_paid_steps() {
+        let mut contract_data = new_contract_data(300);
+        contract_data.allow_skip = true;
+        recompute_schedule(&mut contract_data).unwrap();
+        assert_eq!(next_unpaid_step(&contract_data), 0);
+
+        pay_step(&mut contract_data, 1);
+        assert_eq!(next_unpaid_step(&contract_data), 0);
+        assert_eq!(get_unpaid_steps(&contract_data), vec![0, 2]);
+
+        pay_step(&mut contract_data, 0);
+        assert_eq!(next_unpaid_step(&contract_data), 2);
+        assert_eq!(get_unpaid_steps(&contract_data), vec![2]);
+
+        pay_step(&mut contract_data, 2);
+        assert_eq!(next_unpaid_step(&contract_data), STEPS);
+        assert!(get_unpaid_steps(&contract_data).is_empty());
+    }
+
+    #[test]
+    fn specific_step_needs_allow_skip_and_an_unpaid_step() {
+        let mut contract_data = new_contract_data(300);
+        recompute_schedule(&mut contract_data).unwrap();
+        assert_eq!(validate_specific_step(&contract_data, 2), Err(ContractError::SkipNotAllowed.into()));
+
+        contract_data.allow_skip = true;
+        assert_eq!(validate_specific_step(&contract_data, 2), Ok(2));
+        assert_eq!(validate_specific_step(&contract_data, STEPS), Err(ProgramError::InvalidArgument));
+
+        pay_step(&mut contract_data, 2);
+        assert_eq!(validate_specific_step(&contract_data, 2), Err(ContractError::StepAlreadyPaid.into()));
+    }
+
+    #[test]
+    fn step_records_are_in_step_order() {
+        let mut contract_data = new_contract_data(300);
+
+        for step in [2, 0] {
+            contract_data.paid_order.push(step);
+            contract_data.proofs.push([step as u8; 32]);
+            contract_data.memos.push(format!("memo {}", step));
+            contract_data.evidence_uris.push(format!("uri {}", step));
+        }
+
+        let records = get_step_records(contract_data);
+
+        assert_eq!(records.iter().map(|record| record.step).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(records[0].proof, [0; 32]);
+        assert_eq!(records[0].memo, "memo 0");
+        assert_eq!(records[1].evidence_uri, "uri 2");
+    }
+
+    #[test]
+    fn full_step_records_fit_the_return_data() {
+        let mut contract_data = full_contract_data();
+        contract_data.paid_order = (0..STEPS).collect();
+
+        assert!(borsh::to_vec(&get_step_records(contract_data)).unwrap().len() <= MAX_RETURN_DATA);
+    }
+
+    #[test]
+    fn max_states_fit_the_return_data() {
+        let states = (0..MAX_STATE_CONTRACTS)
+            .map(|_| ContractState {
+                contract: Pubkey::new_unique(),
+                actual_step: u64::MAX,
+                status: ContractStatus::Active,
+                frozen: true,
+                total_quantity: u64::MAX,
+                total_disbursed: u64::MAX,
+                pending_amount: u64::MAX,
+                category: ContractCategory::Retainer,
+            })
+            .collect::<Vec<_>>();
+
+        assert!(borsh::to_vec(&states).unwrap().len() <= MAX_RETURN_DATA);
+    }
+
+    #[test]
+    fn get_state_reads_the_contract_at_its_address() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let worker = Pubkey::new_unique();
+        let (pda_key, mut contract_data) = stored_contract(&program_id, &owner, &worker);
+        contract_data.category = ContractCategory::Bounty;
+        let mut pda = TestAccount::new(pda_key, program_id, 0, account_bytes(&contract_data));
+
+        let state = get_state(&program_id, &pda.info()).unwrap();
+
+        assert_eq!(state.contract, pda_key);
+        assert_eq!(state.total_quantity, 300);
+        assert_eq!(state.category, ContractCategory::Bounty);
+    }
+
+    #[test]
+    fn get_state_rejects_a_contract_at_another_address() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let worker = Pubkey::new_unique();
+        let (_, contract_data) = stored_contract(&program_id, &owner, &worker);
+        let (archive_key, _) = find_archive_address(&program_id, &owner, &worker, &contract_data.contract_id);
+        let mut archive = TestAccount::new(archive_key, program_id, 0, account_bytes(&contract_data));
+
+        assert_eq!(get_state(&program_id, &archive.info()).err(), Some(ContractError::PdaMismatch.into()));
+    }
+
+    #[test]
+    fn confirmations_count_distinct_approvers() {
+        let mut contract_data = new_contract_data(300);
+        contract_data.approvers = unique_keys(3);
+        let approvers = contract_data.approvers.clone();
+
+        add_confirmation(&mut contract_data, &approvers[2]).unwrap();
+        assert_eq!(
+            add_confirmation(&mut contract_data, &approvers[2]),
+            Err(ContractError::AlreadyConfirmed.into())
+        );
+        assert_eq!(get_confirmation_count(&contract_data), 1);
+
+        add_confirmation(&mut contract_data, &approvers[0]).unwrap();
+        assert_eq!(get_confirmation_count(&contract_data), 2);
+        assert_eq!(contract_data.step_confirmers, 0b101);
+
+        assert_eq!(
+            add_confirmation(&mut contract_data, &Pubkey::new_unique()),
+            Err(ContractError::ApproverNotFound.into())
+        );
+    }
+
+    #[test]
+    fn signer_change_drops_revoked_approvals_and_confirmations() {
+        let mut contract_data = new_contract_data(300);
+        contract_data.approvers = unique_keys(3);
+        let approvers = contract_data.approvers.clone();
+
+        for approver in &approvers {
+            add_confirmation(&mut contract_data, approver).unwrap();
+            contract_data.step_approvals.push(*approver);
+        }
+
+        let new_approver = Pubkey::new_unique();
+        set_signers(&mut contract_data, SignerChange {
+            authorized_releasers: Vec::new(),
+            approvers: vec![new_approver, approvers[2], approvers[0]],
+            timelock_expiry: 0,
+        });
+
+        assert_eq!(contract_data.step_approvals, vec![approvers[0], approvers[2]]);
+        assert_eq!(contract_data.step_confirmers, 0b110);
+        assert_eq!(get_confirmation_count(&contract_data), 2);
+        assert_eq!(add_confirmation(&mut contract_data, &approvers[1]), Err(ContractError::ApproverNotFound.into()));
+        add_confirmation(&mut contract_data, &new_approver).unwrap();
+    }
+
+    #[test]
+    fn refund_destination_comes_off_the_allowlist() {
+        let owner_key = Pubkey::new_unique();
+        let allowed_key = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(owner_key);
+        let mut allowed = TestAccount::wallet(allowed_key);
+        let mut stranger = TestAccount::wallet(Pubkey::new_unique());
+        let owner = owner.info();
+        let mut contract_data = new_contract_data(300);
+        let accounts = [stranger.info(), allowed.info()];
+
+        assert_eq!(find_refund_destination(&contract_data, &owner, &accounts).unwrap().key, &owner_key);
+
+        contract_data.refund_allowlist = vec![allowed_key];
+        assert_eq!(find_refund_destination(&contract_data, &owner, &accounts).unwrap().key, &allowed_key);
+        assert_eq!(
+            find_refund_destination(&contract_data, &owner, &accounts[..1]).err(),
+            Some(ContractError::RefundDestinationNotAllowed.into())
+        );
+    }
+
+    #[test]
+    fn sweep_rejects_a_destination_off_the_allowlist() {
+        stub_syscalls();
+
+        let program_id = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let worker_key = Pubkey::new_unique();
+        let (pda_key, mut contract_data) = stored_contract(&program_id, &owner_key, &worker_key);
+        contract_data.refund_allowlist = vec![Pubkey::new_unique()];
+        contract_data.last_activity_ts = TEST_NOW - DEAD_CONTRACT_TIMEOUT;
+        let mut owner = TestAccount::wallet(owner_key);
+        let mut worker = TestAccount::wallet(worker_key);
+        let mut pda = TestAccount::new(pda_key, program_id, 1_000_000_000, account_bytes(&contract_data));
+        let mut stranger = TestAccount::wallet(Pubkey::new_unique());
+        let pda = pda.info();
+        let extra_accounts = [stranger.info()];
+
+        assert_eq!(
+            sweep_abandoned(&owner.info(), &worker.info(), &pda, &extra_accounts, contract_data),
+            Err(ContractError::RefundDestinationNotAllowed.into())
+        );
+        assert_eq!(pda.lamports(), 1_000_000_000);
+    }
+
+    #[test]
+    fn unpack_reads_validate_create_as_the_create_payload() {
+        let data = instruction_data(3, &create_payload("job", 300));
+
+        assert!(matches!(
+            Instruction::unpack(&data),
+            Ok(Instruction::ValidateCreate { payload: CreatePayload { ref contract_id, total_quantity: 300, .. } })
+                if contract_id == "job"
+        ));
+    }
+
+    #[test]
+    fn create_terms_are_validated_like_creation() {
+        let owner = Pubkey::new_unique();
+        let worker = Pubkey::new_unique();
+        let approvers = unique_keys(2);
+        let validate = |payout_delay, notification_tag: &str, breach_compensation_bps, step_recipients: &[Pubkey], step_bps: &[u16], refund_allowlist: &[Pubkey]| {
+            validate_create_terms(
+                &owner,
+                &worker,
+                payout_delay,
+                &[],
+                &approvers,
+                1,
+                refund_allowlist,
+                notification_tag,
+                breach_compensation_bps,
+                step_recipients,
+                step_bps,
+                2
+            )
+        };
+
+        assert_eq!(validate(0, "", 0, &[], &[], &[]), Ok(()));
+        assert_eq!(validate(60, "tag", BPS_DENOMINATOR, &unique_keys(3), &[2_500, 2_500, 5_000], &unique_keys(5)), Ok(()));
+        assert_eq!(validate(-1, "", 0, &[], &[], &[]), Err(ProgramError::InvalidArgument));
+        assert_eq!(
+            validate(0, &"t".repeat(MAX_NOTIFICATION_TAG_LEN + 1), 0, &[], &[], &[]),
+            Err(ContractError::NotificationTagTooLong.into())
+        );
+        assert_eq!(validate(0, "", BPS_DENOMINATOR + 1, &[], &[], &[]), Err(ContractError::InvalidCompensationBps.into()));
+        assert_eq!(validate(0, "", 0, &unique_keys(2), &[], &[]), Err(ContractError::InvalidStepRecipients.into()));
+        assert_eq!(validate(0, "", 0, &[], &[5_000, 5_000], &[]), Err(ContractError::InvalidStepBps.into()));
+        assert_eq!(validate(0, "", 0, &[], &[5_000, 5_000, 1], &[]), Err(ContractError::InvalidStepBps.into()));
+        assert_eq!(
+            validate(0, "", 0, &[], &[], &unique_keys(MAX_REFUND_DESTINATIONS + 1)),
+            Err(ContractError::TooManyRefundDestinations.into())
+        );
+        assert_eq!(
+            validate_create_terms(&owner, &worker, 0, &[], &[owner], 0, &[], "", 0, &[], &[], 0),
+            Err(ContractError::ApproverIsParty.into())
+        );
+    }
+
+    #[test]
+    fn read_contract_data_rejects_foreign_accounts() {
+        let program_id = Pubkey::new_unique();
+        let (pda_key, contract_data) = stored_contract(&program_id, &Pubkey::new_unique(), &Pubkey::new_unique());
+        let mut pda = TestAccount::new(pda_key, Pubkey::new_unique(), 0, account_bytes(&contract_data));
+
+        assert_eq!(read_contract_data(&program_id, &pda.info()).err(), Some(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn read_contract_data_rejects_short_and_corrupt_accounts() {
+        let program_id = Pubkey::new_unique();
+        let (pda_key, contract_data) = stored_contract(&program_id, &Pubkey::new_unique(), &Pubkey::new_unique());
+        let mut data = account_bytes(&contract_data);
+        data.truncate(ContractData::get_account_size("") - 1);
+        let mut short = TestAccount::new(pda_key, program_id, 0, data);
+
+        assert_eq!(read_contract_data(&program_id, &short.info()).err(), Some(ContractError::CorruptData.into()));
+
+        // A contract id claiming more bytes than the account holds.
+        let mut data = account_bytes(&contract_data);
+        data[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut corrupt = TestAccount::new(pda_key, program_id, 0, data);
+
+        assert_eq!(read_contract_data(&program_id, &corrupt.info()).err(), Some(ContractError::CorruptData.into()));
+
+        let mut pda = TestAccount::new(pda_key, program_id, 0, account_bytes(&contract_data));
+
+        assert_eq!(read_contract_data(&program_id, &pda.info()).unwrap().contract_id, contract_data.contract_id);
+    }
+
+    #[test]
+    fn write_contract_data_rejects_a_contract_outgrowing_its_account() {
+        let program_id = Pubkey::new_unique();
+        let (pda_key, contract_data) = stored_contract(&program_id, &Pubkey::new_unique(), &Pubkey::new_unique());
+        let mut data = account_bytes(&contract_data);
+        data.truncate(borsh::to_vec(&contract_data).unwrap().len() - 1);
+        let untouched = data.clone();
+        let mut pda = TestAccount::new(pda_key, program_id, 0, data);
+        let pda = pda.info();
+
+        assert_eq!(write_contract_data(&pda, &contract_data), Err(ContractError::AccountTooSmall.into()));
+        assert_eq!(*pda.data.borrow(), &untouched[..]);
+    }
+
+    #[test]
+    fn write_contract_data_round_trips() {
+        let program_id = Pubkey::new_unique();
+        let (pda_key, mut contract_data) = stored_contract(&program_id, &Pubkey::new_unique(), &Pubkey::new_unique());
+        let mut pda = TestAccount::new(pda_key, program_id, 0, account_bytes(&contract_data));
+        let pda = pda.info();
+
+        contract_data.memos.push("done".to_string());
+        write_contract_data(&pda, &contract_data).unwrap();
+
+        assert_eq!(read_contract_data(&program_id, &pda).unwrap().memos, vec!["done".to_string()]);
+    }
+
+    #[test]
+    fn required_accounts_for_create_and_increment() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let worker = Pubkey::new_unique();
+        let (pda, _) = find_contract_address(&program_id, &owner, &worker, "job");
+
+        let Ok(create) = Instruction::unpack(&instruction_data(0, &create_payload("job", 300))) else {
+            panic!("create payload did not unpack");
+        };
+
+        assert_eq!(
+            required_accounts(&create, &program_id, &owner, &worker),
+            vec![
+                AccountMeta::new(owner, true),
+                AccountMeta::new_readonly(worker, false),
+                AccountMeta::new(pda, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ]
+        );
+
+        let increment = Instruction::IncrementStep { contract_id: "job".to_string(), expected_step: None };
+
+        assert_eq!(
+            required_accounts(&increment, &program_id, &owner, &worker),
+            vec![
+                AccountMeta::new(owner, true),
+                AccountMeta::new(worker, false),
+                AccountMeta::new(pda, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn creation_needs_a_writable_contract_account() {
+        let pda_key = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(Pubkey::new_unique()).signer();
+        let mut read_only_pda = TestAccount::wallet(pda_key).read_only();
+        let mut pda = TestAccount::wallet(pda_key);
+
+        assert_eq!(
+            validate_accounts_on_creation(&owner.info(), &read_only_pda.info(), &pda_key),
+            Err(ContractError::PdaNotWritable.into())
+        );
+        assert_eq!(validate_accounts_on_creation(&owner.info(), &pda.info(), &pda_key), Ok(()));
+    }
+
+    #[test]
+    fn worker_cannot_be_the_program() {
+        let program_id = Pubkey::new_unique();
+        let mut program = TestAccount::wallet(program_id);
+        let mut worker = TestAccount::wallet(Pubkey::new_unique());
+
+        assert_eq!(validate_worker(&program_id, &program.info()), Err(ContractError::WorkerIsProgram.into()));
+        assert_eq!(validate_worker(&program_id, &worker.info()), Ok(()));
+    }
+
+    #[test]
+    fn pda_key_has_to_match() {
+        let pda_key = Pubkey::new_unique();
+        let mut pda = TestAccount::wallet(pda_key);
+        let mut other = TestAccount::wallet(Pubkey::new_unique());
+
+        assert_eq!(validate_pda_key(&pda.info(), &pda_key), Ok(()));
+        assert_eq!(validate_pda_key(&other.info(), &pda_key), Err(ContractError::PdaMismatch.into()));
+    }
+
+    #[test]
+    fn system_program_has_to_be_the_real_one() {
+        let mut system_program = TestAccount::wallet(system_program::ID);
+        let mut impostor = TestAccount::wallet(Pubkey::new_unique());
+
+        assert_eq!(validate_system_program(&system_program.info()), Ok(()));
+        assert_eq!(validate_system_program(&impostor.info()), Err(ProgramError::IncorrectProgramId));
+    }
+
+    #[test]
+    fn dual_increment_needs_owner_and_worker() {
+        let releaser_key = Pubkey::new_unique();
+        let mut contract_data = new_contract_data(300);
+        contract_data.dual_increment = true;
+        contract_data.authorized_releasers = vec![releaser_key];
+        let mut owner = TestAccount::wallet(Pubkey::new_unique());
+        let mut signing_owner = TestAccount::wallet(Pubkey::new_unique()).signer();
+        let mut worker = TestAccount::wallet(Pubkey::new_unique());
+        let mut signing_worker = TestAccount::wallet(Pubkey::new_unique()).signer();
+        let mut releaser = TestAccount::wallet(releaser_key).signer();
+        let releasers = [releaser.info()];
+
+        assert_eq!(
+            validate_increment_authority(&signing_owner.info(), &signing_worker.info(), &[], &contract_data),
+            Ok(())
+        );
+        assert_eq!(
+            validate_increment_authority(&signing_owner.info(), &worker.info(), &[], &contract_data),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(
+            validate_increment_authority(&owner.info(), &signing_worker.info(), &[], &contract_data),
+            Err(ProgramError::IllegalOwner)
+        );
+
+        // A releaser stands in for the owner only outside dual mode.
+        assert_eq!(
+            validate_increment_authority(&owner.info(), &signing_worker.info(), &releasers, &contract_data),
+            Err(ProgramError::IllegalOwner)
+        );
+
+        contract_data.dual_increment = false;
+        assert_eq!(validate_increment_authority(&owner.info(), &worker.info(), &releasers, &contract_data), Ok(()));
+    }
+
+    #[test]
+    fn delegate_can_increment_until_expiry() {
+        stub_syscalls();
+
+        let delegate_key = Pubkey::new_unique();
+        let mut contract_data = new_contract_data(300);
+        let mut owner = TestAccount::wallet(Pubkey::new_unique());
+        let mut worker = TestAccount::wallet(Pubkey::new_unique());
+        let mut delegate = TestAccount::wallet(delegate_key).signer();
+        let delegates = [delegate.info()];
+
+        contract_data.delegated_increment = Some((delegate_key, TEST_NOW));
+        assert_eq!(validate_increment_authority(&owner.info(), &worker.info(), &delegates, &contract_data), Ok(()));
+
+        contract_data.delegated_increment = Some((delegate_key, TEST_NOW - 1));
+        assert_eq!(
+            validate_increment_authority(&owner.info(), &worker.info(), &delegates, &contract_data),
+            Err(ContractError::DelegationExpired.into())
+        );
+
+        contract_data.delegated_increment = Some((Pubkey::new_unique(), TEST_NOW));
+        assert_eq!(
+            validate_increment_authority(&owner.info(), &worker.info(), &delegates, &contract_data),
+            Err(ProgramError::IllegalOwner)
+        );
+    }
+
+    #[test]
+    fn delegation_has_to_expire_in_the_future() {
+        stub_syscalls();
+
+        let program_id = Pubkey::new_unique();
+        let delegate = Pubkey::new_unique();
+        let (pda_key, contract_data) = stored_contract(&program_id, &Pubkey::new_unique(), &Pubkey::new_unique());
+        let mut pda = TestAccount::new(pda_key, program_id, 0, account_bytes(&contract_data));
+        let pda = pda.info();
+
+        assert_eq!(
+            delegate_increment(&pda, delegate, TEST_NOW, read_contract_data(&program_id, &pda).unwrap()),
+            Err(ContractError::DelegationExpired.into())
+        );
+
+        delegate_increment(&pda, delegate, TEST_NOW + 60, read_contract_data(&program_id, &pda).unwrap()).unwrap();
+
+        assert_eq!(
+            read_contract_data(&program_id, &pda).unwrap().delegated_increment,
+            Some((delegate, TEST_NOW + 60))
+        );
+    }
+
+    #[test]
+    fn batch_cancel_checks_the_batch_before_any_account() {
+        let program_id = Pubkey::new_unique();
+        let contract_ids = |count: usize| (0..count).map(|id| id.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            batch_cancel_handler(&program_id, &[], contract_ids(MAX_BATCH_SIZE + 1)),
+            Err(ContractError::BatchTooLarge.into())
+        );
+        assert_eq!(batch_cancel_handler(&program_id, &[], Vec::new()), Err(ProgramError::InvalidArgument));
+        assert_eq!(
+            batch_cancel_handler(&program_id, &[], contract_ids(MAX_BATCH_SIZE)),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+
+    fn projected_timestamps(projection: &[ProjectedStep]) -> Vec<(u64, u64, i64)> {
+        projection
+            .iter()
+            .map(|projected| (projected.step, projected.amount, projected.earliest_timestamp))
+            .collect()
+    }
+
+    #[test]
+    fn projection_waits_a_delay_per_step() {
+        let mut contract_data = new_contract_data(300);
+        recompute_schedule(&mut contract_data).unwrap();
+
+        assert_eq!(
+            projected_timestamps(&project_remaining(&contract_data, 1_000).unwrap()),
+            vec![(0, 100, 1_000), (1, 100, 1_000), (2, 100, 1_000)]
+        );
+
+        contract_data.payout_delay = 100;
+        assert_eq!(
+            projected_timestamps(&project_remaining(&contract_data, 1_000).unwrap()),
+            vec![(0, 100, 1_100), (1, 100, 1_200), (2, 100, 1_300)]
+        );
+    }
+
+    #[test]
+    fn projection_starts_after_the_held_payout() {
+        let mut contract_data = new_contract_data(300);
+        contract_data.allow_skip = true;
+        contract_data.payout_delay = 100;
+        recompute_schedule(&mut contract_data).unwrap();
+
+        // Step 1 paid and held since 950, releasable from 1050.
+        contract_data.paid_steps |= 1 << 1;
+        contract_data.actual_step = 1;
+        contract_data.pending_amount = 100;
+        contract_data.pending_since = 950;
+        contract_data.pending_step = 1;
+
+        assert_eq!(
+            projected_timestamps(&project_remaining(&contract_data, 1_000).unwrap()),
+            vec![(0, 100, 1_150), (2, 100, 1_250)]
+        );
+
+        // A held payout already releasable doesn't hold the projection back.
+        assert_eq!(
+            projected_timestamps(&project_remaining(&contract_data, 2_000).unwrap()),
+            vec![(0, 100, 2_100), (2, 100, 2_200)]
+        );
+    }
+
+    #[test]
+    fn verify_reports_each_check() {
+        stub_syscalls();
+
+        let program_id = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let worker_key = Pubkey::new_unique();
+        let (pda_key, contract_data) = stored_contract(&program_id, &owner_key, &worker_key);
+        let data = account_bytes(&contract_data);
+        let funded = Rent::default().minimum_balance(data.len()) + contract_data.total_quantity;
+        let mut owner = TestAccount::wallet(owner_key);
+        let mut worker = TestAccount::wallet(worker_key);
+        let (owner, worker) = (owner.info(), worker.info());
+        let all_checks = VERIFY_PROGRAM_OWNED
+            | VERIFY_DATA_READABLE
+            | VERIFY_STEP_IN_RANGE
+            | VERIFY_ESCROW_CONSISTENT
+            | VERIFY_SEEDS_MATCH;
+
+        let mut pda = TestAccount::new(pda_key, program_id, funded, data.clone());
+        assert_eq!(verify(&program_id, &owner, &worker, &pda.info(), "contract"), Ok(all_checks));
+        assert_eq!(
+            verify(&program_id, &owner, &worker, &pda.info(), "other"),
+            Ok(all_checks & !VERIFY_SEEDS_MATCH)
+        );
+
+        let mut underfunded = TestAccount::new(pda_key, program_id, funded - 1, data.clone());
+        assert_eq!(
+            verify(&program_id, &owner, &worker, &underfunded.info(), "contract"),
+            Ok(all_checks & !VERIFY_ESCROW_CONSISTENT)
+        );
+
+        let mut unreadable = TestAccount::new(pda_key, program_id, funded, vec![0; 8]);
+        assert_eq!(verify(&program_id, &owner, &worker, &unreadable.info(), "contract"), Ok(VERIFY_PROGRAM_OWNED));
+
+        let mut foreign = TestAccount::new(pda_key, Pubkey::new_unique(), funded, data);
+        assert_eq!(verify(&program_id, &owner, &worker, &foreign.info(), "contract"), Ok(0));
+    }
+
+    #[test]
+    fn claimable_is_the_held_payout_once_its_delay_elapsed() {
+        stub_syscalls();
+
+        let program_id = Pubkey::new_unique();
+        let worker_key = Pubkey::new_unique();
+        let (pda_key, mut contract_data) = stored_contract(&program_id, &Pubkey::new_unique(), &worker_key);
+        let mut worker = TestAccount::wallet(worker_key);
+        let mut stranger = TestAccount::wallet(Pubkey::new_unique());
+        let worker = worker.info();
+        let claimable = |contract_data: &ContractData| {
+            let mut pda = TestAccount::new(pda_key, program_id, 0, account_bytes(contract_data));
+            get_claimable(&program_id, &worker, &pda.info()).map(|payout| (payout.contract, payout.amount))
+        };
+
+        assert_eq!(claimable(&contract_data), Ok((pda_key, 0)));
+
+        contract_data.payout_delay = 100;
+        contract_data.pending_amount = 100;
+        contract_data.pending_since = TEST_NOW - 99;
+        assert_eq!(claimable(&contract_data), Ok((pda_key, 0)));
+
+        contract_data.pending_since = TEST_NOW - 100;
+        assert_eq!(claimable(&contract_data), Ok((pda_key, 100)));
+
+        let mut pda = TestAccount::new(pda_key, program_id, 0, account_bytes(&contract_data));
+        assert_eq!(
+            get_claimable(&program_id, &stranger.info(), &pda.info()).err(),
+            Some(ContractError::WorkerMismatch.into())
+        );
+    }
+
+    #[test]
+    fn claimable_rejects_a_contract_at_another_address() {
+        let program_id = Pubkey::new_unique();
+        let worker_key = Pubkey::new_unique();
+        let (_, contract_data) = stored_contract(&program_id, &Pubkey::new_unique(), &worker_key);
+        let mut worker = TestAccount::wallet(worker_key);
+        let mut pda = TestAccount::new(Pubkey::new_unique(), program_id, 0, account_bytes(&contract_data));
+
+        assert_eq!(
+            get_claimable(&program_id, &worker.info(), &pda.info()).err(),
+            Some(ContractError::PdaMismatch.into())
+        );
+    }
+
+    #[test]
+    fn exists_reports_whether_the_contract_account_is_ours() {
+        stub_syscalls();
+
+        let program_id = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let worker_key = Pubkey::new_unique();
+        let (pda_key, contract_data) = stored_contract(&program_id, &owner_key, &worker_key);
+        let exists = |pda: &mut TestAccount| {
+            let mut owner = TestAccount::wallet(owner_key);
+            let mut worker = TestAccount::wallet(worker_key);
+
+            exists_handler(&program_id, &[owner.info(), worker.info(), pda.info()], "contract".to_string())
+                .map(|_| returned_data())
+        };
+
+        assert_eq!(exists(&mut TestAccount::wallet(pda_key)), Ok(vec![0]));
+        assert_eq!(exists(&mut TestAccount::new(pda_key, program_id, 0, account_bytes(&contract_data))), Ok(vec![1]));
+        assert_eq!(
+            exists(&mut TestAccount::wallet(Pubkey::new_unique())),
+            Err(ContractError::PdaMismatch.into())
+        );
+    }
+
+    #[test]
+    fn signer_change_applies_only_after_its_timelock() {
+        stub_syscalls();
+
+        let program_id = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let worker_key = Pubkey::new_unique();
+        let (pda_key, contract_data) = stored_contract(&program_id, &owner_key, &worker_key);
+        let (releasers, approvers) = (unique_keys(2), unique_keys(1));
+        let mut owner = TestAccount::wallet(owner_key);
+        let mut worker = TestAccount::wallet(worker_key);
+        let mut pda = TestAccount::new(pda_key, program_id, 0, account_bytes(&contract_data));
+        let (owner, worker, pda) = (owner.info(), worker.info(), pda.info());
+        let read = || read_contract_data(&program_id, &pda).unwrap();
+
+        propose_signer_change(&owner, &worker, &pda, releasers.clone(), approvers.clone(), read()).unwrap();
+        assert!(matches!(
+            read().pending_signer_change,
+            Some(SignerChange { timelock_expiry, .. }) if timelock_expiry == TEST_NOW + SIGNER_CHANGE_DELAY_SECONDS
+        ));
+        assert_eq!(
+            propose_signer_change(&owner, &worker, &pda, Vec::new(), Vec::new(), read()),
+            Err(ContractError::SignerChangeAlreadyProposed.into())
+        );
+        assert_eq!(apply_signer_change(&pda, read()), Err(ContractError::SignerChangeStillLocked.into()));
+        assert!(read().authorized_releasers.is_empty());
+
+        // Once the clock reaches the expiry.
+        let mut unlocked = read();
+        if let Some(signer_change) = unlocked.pending_signer_change.as_mut() {
+            signer_change.timelock_expiry = TEST_NOW;
+        }
+        apply_signer_change(&pda, unlocked).unwrap();
+
+        let applied = read();
+        assert_eq!(applied.authorized_releasers, releasers);
+        assert_eq!(applied.approvers, approvers);
+        assert!(applied.pending_signer_change.is_none());
+    }
+
+    #[test]
+    fn signer_change_can_be_cancelled_in_its_window() {
+        stub_syscalls();
+
+        let program_id = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let worker_key = Pubkey::new_unique();
+        let (pda_key, contract_data) = stored_contract(&program_id, &owner_key, &worker_key);
+        let mut owner = TestAccount::wallet(owner_key);
+        let mut worker = TestAccount::wallet(worker_key);
+        let mut pda = TestAccount::new(pda_key, program_id, 0, account_bytes(&contract_data));
+        let (owner, worker, pda) = (owner.info(), worker.info(), pda.info());
+        let read = || read_contract_data(&program_id, &pda).unwrap();
+
+        propose_signer_change(&owner, &worker, &pda, unique_keys(1), Vec::new(), read()).unwrap();
+        cancel_signer_change(&pda, read()).unwrap();
+
+        assert!(read().pending_signer_change.is_none());
+        assert!(read().authorized_releasers.is_empty());
+        assert_eq!(cancel_signer_change(&pda, read()), Err(ContractError::SignerChangeNotProposed.into()));
+        assert_eq!(apply_signer_change(&pda, read()), Err(ContractError::SignerChangeNotProposed.into()));
+    }
+
+    #[test]
+    fn only_dust_into_a_closed_account_is_a_revival() {
+        stub_syscalls();
+
+        let rent_exempt = Rent::default().minimum_balance(0);
+        let mut closed = TestAccount::wallet(Pubkey::new_unique());
+        let mut open = TestAccount::new(Pubkey::new_unique(), system_program::ID, 1, Vec::new());
+        let (closed, open) = (closed.info(), open.info());
+
+        assert_eq!(is_dust_revival(&closed, rent_exempt - 1), Ok(true));
+        assert_eq!(is_dust_revival(&closed, rent_exempt), Ok(false));
+        assert_eq!(is_dust_revival(&open, 1), Ok(false));
+    }
+
+    #[test]
+    fn dust_revival_is_held_until_the_worker_is_funded() {
+        stub_syscalls();
+
+        let program_id = Pubkey::new_unique();
+        let worker_key = Pubkey::new_unique();
+        let (pda_key, mut contract_data) = stored_contract(&program_id, &Pubkey::new_unique(), &worker_key);
+        contract_data.pending_amount = 100;
+        let mut worker = TestAccount::wallet(worker_key);
+        let mut pda = TestAccount::new(pda_key, program_id, 1_000_000_000, account_bytes(&contract_data));
+
+        let mut dust = new_contract_data(300);
+        recompute_schedule(&mut dust).unwrap();
+        assert!(quote_increment(&dust, 0, &worker.info()).unwrap().held);
+
+        let mut sufficient = new_contract_data(3 * Rent::default().minimum_balance(0));
+        recompute_schedule(&mut sufficient).unwrap();
+        assert!(!quote_increment(&sufficient, 0, &worker.info()).unwrap().held);
+
+        assert_eq!(
+            pay_pending(&pda.info(), &worker.info(), &mut contract_data),
+            Err(ContractError::WorkerAccountNotFunded.into())
+        );
+        assert_eq!(contract_data.pending_amount, 100);
+        assert_eq!(pda.lamports, 1_000_000_000);
+    }
+
+    #[test]
+    fn get_limits_returns_the_program_limits() {
+        stub_syscalls();
+
+        get_limits().unwrap();
+
+        let limits = ProgramLimits::try_from_slice(&returned_data()).unwrap();
+        assert_eq!(limits.steps, STEPS);
+        assert_eq!(limits.max_contract_id_len, MAX_CONTRACT_ID_LEN as u64);
+        assert_eq!(limits.max_batch_size, MAX_BATCH_SIZE as u64);
+        assert_eq!(limits.signer_change_delay_seconds, SIGNER_CHANGE_DELAY_SECONDS);
+    }
+
+    #[test]
+    fn contract_id_is_taken_until_the_account_is_emptied() {
+        let pda_key = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(Pubkey::new_unique()).signer();
+        let mut funded = TestAccount::new(pda_key, system_program::ID, 1, Vec::new());
+        let mut cancelled = TestAccount::new(pda_key, Pubkey::new_unique(), 0, vec![0; 8]);
+        let mut closed = TestAccount::wallet(pda_key);
+        let owner = owner.info();
+
+        assert_eq!(
+            validate_accounts_on_creation(&owner, &funded.info(), &pda_key),
+            Err(ContractError::ContractAlreadyExists.into())
+        );
+        assert_eq!(
+            validate_accounts_on_creation(&owner, &cancelled.info(), &pda_key),
+            Err(ContractError::ContractAlreadyExists.into())
+        );
+        assert_eq!(validate_accounts_on_creation(&owner, &closed.info(), &pda_key), Ok(()));
+    }
+
+    #[test]
+    fn steps_pay_their_own_recipients() {
+        let recipient_keys = unique_keys(STEPS as usize);
+        let mut contract_data = new_contract_data(300);
+        let mut worker = TestAccount::wallet(Pubkey::new_unique());
+        let mut recipients = recipient_keys.iter().map(|&key| TestAccount::wallet(key)).collect::<Vec<_>>();
+        let worker = worker.info();
+        let accounts = recipients.iter_mut().map(TestAccount::info).collect::<Vec<_>>();
+
+        assert_eq!(find_step_recipient(&contract_data, 1, &worker, &accounts).unwrap().key, worker.key);
+
+        contract_data.step_recipients = recipient_keys.clone();
+        assert_eq!(find_step_recipient(&contract_data, 1, &worker, &accounts).unwrap().key, &recipient_keys[1]);
+        assert_eq!(
+            find_step_recipient(&contract_data, 2, &worker, &accounts[..2]).err(),
+            Some(ProgramError::NotEnoughAccountKeys)
+        );
+
+        contract_data.pending_step = 2;
+        assert_eq!(find_held_step_recipient(&contract_data, &worker, &accounts).unwrap().key, &recipient_keys[2]);
     }
 }